@@ -0,0 +1,122 @@
+//! 编解码吞吐量基准，运行 `cargo bench`
+//!
+//! 没有引入 criterion，每项重复运行若干次，取最快的一次换算成 MB/s
+
+use std::hint::black_box;
+use std::time::{Duration, Instant};
+
+use rust_huffman::{ByteHuffmanCodec, DecodeConfig, HuffmanCodec};
+
+/// 每份语料的大小
+const CORPUS_SIZE: usize = 4 * 1024 * 1024;
+/// 每项重复运行的次数
+const ROUNDS: usize = 5;
+
+/// 英文文本：按大致的词频重复常用单词
+fn english() -> String {
+    let words = [
+        "the", "of", "and", "to", "a", "in", "is", "that", "for", "it", "as", "was", "with", "be", "by",
+        "on", "not", "he", "this", "are", "or", "his", "from", "at", "which", "but", "have", "an", "they",
+        "huffman", "coding", "compression", "tree", "symbol", "frequency",
+    ];
+    let mut text = String::with_capacity(CORPUS_SIZE);
+    let mut rng = 0x2545_F491_4F6C_DD1Du64;
+    while text.len() < CORPUS_SIZE {
+        rng ^= rng << 13;
+        rng ^= rng >> 7;
+        rng ^= rng << 17;
+        // 取两个随机数中较小的一个，使靠前的单词更常见
+        let a = (rng % words.len() as u64) as usize;
+        let b = ((rng >> 32) % words.len() as u64) as usize;
+        text += words[a.min(b)];
+        text += if rng.is_multiple_of(13) { ".\n" } else { " " };
+    }
+    text
+}
+
+/// 源代码：重复本库自身的源文件
+fn source_code() -> String {
+    let code = include_str!("../src/huffman.rs");
+    code.repeat(CORPUS_SIZE / code.len() + 1)
+}
+
+/// 随机字节，几乎无法压缩
+fn random_bytes() -> Vec<u8> {
+    let mut rng = 0x9E37_79B9_7F4A_7C15u64;
+    (0..CORPUS_SIZE)
+        .map(|_| {
+            rng ^= rng << 13;
+            rng ^= rng >> 7;
+            rng ^= rng << 17;
+            rng as u8
+        })
+        .collect()
+}
+
+/// 运行 `ROUNDS` 次，返回最短耗时
+fn fastest(mut f: impl FnMut()) -> Duration {
+    (0..ROUNDS)
+        .map(|_| {
+            let start = Instant::now();
+            f();
+            start.elapsed()
+        })
+        .min()
+        .unwrap()
+}
+
+fn report(name: &str, op: &str, bytes: usize, time: Duration) {
+    let mb_per_sec = bytes as f64 / (1024.0 * 1024.0) / time.as_secs_f64();
+    println!("{:<12} {:<8} {:>10.2} MB/s  ({:?})", name, op, mb_per_sec, time);
+}
+
+fn bench_text(name: &str, text: &str) {
+    let (payload, config) = HuffmanCodec::encode(text).unwrap();
+    let decode_map = DecodeConfig::build(&config).unwrap();
+    report(name, "encode", text.len(), fastest(|| {
+        black_box(HuffmanCodec::encode(black_box(text)).unwrap());
+    }));
+    report(name, "decode", text.len(), fastest(|| {
+        black_box(HuffmanCodec::decode(black_box(&payload), &decode_map).unwrap());
+    }));
+}
+
+/// 逐条编码大量短消息，比较每次新建与复用编解码器实例
+fn bench_messages(name: &str, messages: &[String]) {
+    let bytes = messages.iter().map(String::len).sum();
+    report(name, "encode", bytes, fastest(|| {
+        for message in messages {
+            black_box(HuffmanCodec::encode(black_box(message)).unwrap());
+        }
+    }));
+    let mut codec = HuffmanCodec::new();
+    let mut out = vec![];
+    report(name, "reuse", bytes, fastest(|| {
+        for message in messages {
+            out.clear();
+            black_box(codec.encode_into(black_box(message), &mut out).unwrap());
+        }
+    }));
+}
+
+fn bench_bytes(name: &str, bytes: &[u8]) {
+    let archive = ByteHuffmanCodec::encode_bytes(bytes).unwrap();
+    report(name, "encode", bytes.len(), fastest(|| {
+        black_box(ByteHuffmanCodec::encode_bytes(black_box(bytes)).unwrap());
+    }));
+    report(name, "decode", bytes.len(), fastest(|| {
+        black_box(ByteHuffmanCodec::decode_bytes(black_box(&archive)).unwrap());
+    }));
+}
+
+fn main() {
+    // `cargo test --benches` 会以 `--bench` 之外的参数运行，此时只检查能否运行，不计时
+    if !std::env::args().any(|arg| arg == "--bench") {
+        return;
+    }
+    bench_text("english", &english());
+    bench_text("source", &source_code());
+    bench_bytes("random", &random_bytes());
+    let messages = english().lines().take(20_000).map(str::to_owned).collect::<Vec<_>>();
+    bench_messages("messages", &messages);
+}
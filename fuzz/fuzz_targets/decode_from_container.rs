@@ -0,0 +1,10 @@
+//! 把任意字节当作压缩文件解压：格式有误时只能返回错误，不能 panic
+
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use rust_huffman::HuffmanCodec;
+
+fuzz_target!(|data: &[u8]| {
+    let _ = HuffmanCodec::decode_from_container(data);
+});
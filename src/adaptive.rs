@@ -0,0 +1,204 @@
+//! 自适应哈夫曼编码（FGK 算法）
+//!
+//! 编码与解码双方从只有一个 NYT（尚未出现）结点的树开始，每处理一个字符就按相同规则更新树，
+//! 因此只需读一遍输入，也不需要配置：
+//!
+//! - 已出现过的字符写出其当前编码；
+//! - 第一次出现的字符写出 NYT 的编码，再写出 21 位码点；
+//! - 结束时写出 NYT 的编码和码点 `0x110000`（不是合法字符），最后补 0 凑满字节。
+//!
+//! 更新时要交换结点在树中的位置，结点保存在数组中、以下标互相引用，
+//! 与 [`HuffmanTree::build`](crate::HuffmanTree::build) 先分配结点数组再连接的做法一致。
+
+use std::collections::HashMap;
+
+use crate::bits::{BitReader, BitWriter};
+use crate::huffman::{HuffmanError, Weight};
+
+/// 码点的位数，足以表示所有 Unicode 码点和结束标记
+const SYMBOL_BITS: u32 = 21;
+/// 结束标记，紧跟在 NYT 编码之后
+const END: u32 = 0x11_0000;
+
+/// 自适应哈夫曼编解码器，输出不附带配置，也不需要两遍扫描
+pub struct AdaptiveHuffmanCodec;
+
+impl AdaptiveHuffmanCodec {
+    /// 自适应哈夫曼编码
+    pub fn encode(source: &str) -> Vec<u8> {
+        let mut tree = AdaptiveTree::new();
+        let mut out = vec![];
+        let mut writer = BitWriter::new(&mut out);
+        // 写入内存不会失败
+        let mut push = |bits: &[bool]| writer.write_bits(bits).unwrap();
+        for ch in source.chars() {
+            match tree.leaf(ch) {
+                Some(leaf) => push(&tree.code(leaf)),
+                None => {
+                    push(&tree.code(tree.nyt));
+                    push(&symbol_bits(ch as u32));
+                },
+            }
+            tree.update(ch);
+        }
+        push(&tree.code(tree.nyt));
+        push(&symbol_bits(END));
+        writer.finish().unwrap();
+        out
+    }
+
+    /// 解码由 [`encode`](Self::encode) 生成的数据
+    ///
+    /// 数据在结束标记之前用完时返回 `UnexpectedEof`，结束标记之后还有非 0 的位或多余的字节时返回
+    /// `InvalidPadding`
+    pub fn decode(source: &[u8]) -> Result<String, HuffmanError> {
+        let mut tree = AdaptiveTree::new();
+        // 结束标记之后只能是凑满最后一个字节的补位 0
+        let mut bits = BitReader::new(source, 0);
+        let mut next_bit = || bits.read_bit()?.ok_or(HuffmanError::UnexpectedEof);
+        let mut result = String::new();
+        loop {
+            // 从根结点沿树走到叶子结点
+            let mut node = tree.root;
+            while let Some(children) = tree.nodes[node].children {
+                let bit = next_bit()?;
+                node = children[bit as usize];
+            }
+            let ch = match tree.nodes[node].symbol {
+                Some(ch) => ch,
+                None => {
+                    let mut code = 0;
+                    for _ in 0..SYMBOL_BITS {
+                        code = (code << 1) | next_bit()? as u32;
+                    }
+                    if code == END {
+                        break;
+                    }
+                    char::from_u32(code)
+                        .ok_or_else(|| HuffmanError::MalformedConfig(format!("无效的码点：{:#X}", code)))?
+                },
+            };
+            result.push(ch);
+            tree.update(ch);
+        }
+        bits.finish()?;
+        Ok(result)
+    }
+}
+
+/// 把码点写成高位在前的 21 位
+fn symbol_bits(code: u32) -> Vec<bool> {
+    (0..SYMBOL_BITS).rev().map(|i| (code >> i) & 1 == 1).collect()
+}
+
+struct Node {
+    weight: Weight,
+    parent: Option<usize>,
+    /// 左(0)右(1)子结点，叶子结点为 `None`
+    children: Option<[usize; 2]>,
+    /// 叶子结点的字符，NYT 结点为 `None`
+    symbol: Option<char>,
+}
+
+/// FGK 算法维护的树
+///
+/// `order` 按结点编号从大到小排列所有结点，始终满足兄弟性质：权重单调不增，
+/// 且每个结点与其兄弟相邻，因此同一权重的结点在 `order` 中连成一段
+struct AdaptiveTree {
+    nodes: Vec<Node>,
+    order: Vec<usize>,
+    /// 结点在 `order` 中的位置
+    position: Vec<usize>,
+    /// 字符对应的叶子结点
+    leaves: HashMap<char, usize>,
+    root: usize,
+    nyt: usize,
+}
+
+impl AdaptiveTree {
+    fn new() -> Self {
+        let nyt = Node { weight: 0, parent: None, children: None, symbol: None };
+        Self {
+            nodes: vec![nyt],
+            order: vec![0],
+            position: vec![0],
+            leaves: HashMap::new(),
+            root: 0,
+            nyt: 0,
+        }
+    }
+
+    fn leaf(&self, ch: char) -> Option<usize> {
+        self.leaves.get(&ch).copied()
+    }
+
+    /// 从叶子结点回溯到根结点得到编码
+    fn code(&self, mut node: usize) -> Vec<bool> {
+        let mut code = vec![];
+        while let Some(parent) = self.nodes[node].parent {
+            code.push(self.nodes[parent].children.unwrap()[1] == node);
+            node = parent;
+        }
+        code.reverse();
+        code
+    }
+
+    fn push_node(&mut self, node: Node) -> usize {
+        let index = self.nodes.len();
+        self.nodes.push(node);
+        self.position.push(self.order.len());
+        self.order.push(index);
+        index
+    }
+
+    /// 处理一个字符后更新树
+    fn update(&mut self, ch: char) {
+        let mut node = match self.leaf(ch) {
+            Some(leaf) => leaf,
+            None => {
+                // 原 NYT 结点分裂成内部结点，左子结点为新的 NYT，右子结点为新字符
+                let parent = self.nyt;
+                let child = |symbol| Node { weight: 0, parent: Some(parent), children: None, symbol };
+                let leaf = self.push_node(child(Some(ch)));
+                let nyt = self.push_node(child(None));
+                self.nodes[parent].children = Some([nyt, leaf]);
+                self.nyt = nyt;
+                self.leaves.insert(ch, leaf);
+                leaf
+            },
+        };
+        loop {
+            // 与同一权重中编号最大的结点交换，再增加权重，保持兄弟性质。
+            // 新字符的叶子结点刚加一时暂时排在其权重为 0 的父结点之后，
+            // 因此从当前结点向前查找，而不是在整个 `order` 中二分查找
+            let weight = self.nodes[node].weight;
+            let mut first = self.position[node];
+            while first > 0 && self.nodes[self.order[first - 1]].weight == weight {
+                first -= 1;
+            }
+            let leader = self.order[first];
+            if leader != node && Some(leader) != self.nodes[node].parent {
+                self.swap(node, leader);
+            }
+            self.nodes[node].weight += 1;
+            match self.nodes[node].parent {
+                Some(parent) => node = parent,
+                None => break,
+            }
+        }
+    }
+
+    /// 交换两棵子树在树中的位置及其编号
+    fn swap(&mut self, a: usize, b: usize) {
+        let (pa, pb) = (self.nodes[a].parent.unwrap(), self.nodes[b].parent.unwrap());
+        let side = |parent: usize, node: usize| (self.nodes[parent].children.unwrap()[1] == node) as usize;
+        let (sa, sb) = (side(pa, a), side(pb, b));
+        self.nodes[pa].children.as_mut().unwrap()[sa] = b;
+        self.nodes[pb].children.as_mut().unwrap()[sb] = a;
+        self.nodes[a].parent = Some(pb);
+        self.nodes[b].parent = Some(pa);
+        let (qa, qb) = (self.position[a], self.position[b]);
+        self.order.swap(qa, qb);
+        self.position.swap(a, b);
+    }
+}
@@ -0,0 +1,284 @@
+//! 按位读写，编码与解码共用，默认高位在前，也可选择低位在前（见 [`BitOrder`]）
+//!
+//! 编码的位数一般不是 8 的倍数，最后一个字节中最后写入的几位补 0，补位数量记录在配置的 `space` 中。
+//! [`BitWriter`] 写出时补齐最后一个字节并返回补位数量，[`BitReader`] 读取时据此跳过并检查补位。
+
+use alloc::{vec, vec::Vec};
+
+#[cfg(feature = "std")]
+use std::io::{self, ErrorKind, Read, Write};
+
+#[cfg(not(feature = "std"))]
+use self::io::{Read, Write};
+use crate::huffman::HuffmanError;
+
+/// 缓冲区大小，凑满后才写给内层，避免逐字节写入
+const BUF_SIZE: usize = 8 * 1024;
+
+/// 共 `total_bits` 位的编码凑满整字节所需的补位数量，即配置中的 `space`，总在 `0..8` 之内
+pub fn padding_bits(total_bits: usize) -> u8 {
+    ((8 - total_bits % 8) % 8) as u8
+}
+
+/// 字节内的位序，即先写入的位放在字节的高位还是低位
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum BitOrder {
+    /// 高位在前，默认位序，与旧版本的压缩文件兼容
+    #[default]
+    MsbFirst,
+    /// 低位在前，用于与按这种位序读写的外部工具交换数据
+    LsbFirst,
+}
+
+impl BitOrder {
+    /// 字节中第 `i` 个写入的位
+    pub(crate) fn mask(self, i: u8) -> u8 {
+        match self {
+            BitOrder::MsbFirst => 0x80 >> i,
+            BitOrder::LsbFirst => 1 << i,
+        }
+    }
+
+    /// 字节中第 `i` 个及之后写入的所有位，`i` 为 8 时为 0
+    fn tail_mask(self, i: u8) -> u8 {
+        match self {
+            BitOrder::MsbFirst => (0xFFu16 >> i) as u8,
+            BitOrder::LsbFirst => (0xFFu16 << i) as u8,
+        }
+    }
+}
+
+/// 逐位写出到 `W`，凑满 8 位组成一个字节
+pub struct BitWriter<W: Write> {
+    inner: W,
+    buf: Vec<u8>,
+    /// 尚未凑满一个字节的位
+    byte: u8,
+    /// `byte` 中已有的位数
+    count: u8,
+    order: BitOrder,
+}
+
+impl<W: Write> BitWriter<W> {
+    /// 按高位在前的位序写出
+    pub fn new(inner: W) -> Self {
+        Self::with_order(inner, BitOrder::MsbFirst)
+    }
+
+    pub fn with_order(inner: W, order: BitOrder) -> Self {
+        Self { inner, buf: Vec::with_capacity(BUF_SIZE), byte: 0, count: 0, order }
+    }
+
+    /// 写入一位
+    pub fn write_bit(&mut self, bit: bool) -> io::Result<()> {
+        if bit {
+            self.byte |= self.order.mask(self.count);
+        }
+        self.count += 1;
+        if self.count == 8 {
+            self.buf.push(self.byte);
+            self.byte = 0;
+            self.count = 0;
+            if self.buf.len() >= BUF_SIZE {
+                self.inner.write_all(&self.buf)?;
+                self.buf.clear();
+            }
+        }
+        Ok(())
+    }
+
+    /// 依次写入多位，如一个字符的编码，长度不限
+    pub fn write_bits(&mut self, bits: &[bool]) -> io::Result<()> {
+        bits.iter().try_for_each(|bit| self.write_bit(*bit))
+    }
+
+    /// 补 0 凑满最后一个字节并全部写出，返回补位数量
+    pub fn finish(mut self) -> io::Result<u8> {
+        let space = padding_bits(self.count as usize);
+        if self.count != 0 {
+            self.buf.push(self.byte);
+        }
+        self.inner.write_all(&self.buf)?;
+        self.inner.flush()?;
+        Ok(space)
+    }
+}
+
+/// 从 `R` 逐位读取，最后一个字节中最后写入的 `space` 位是补位，不会读出
+///
+/// 为了判断哪个字节是最后一个，总是预先读入当前字节之后的数据，因此可能多读入 `R` 中压缩数据之后的内容
+pub struct BitReader<R: Read> {
+    inner: R,
+    buf: Vec<u8>,
+    /// `buf[start..end]` 为尚未读完的字节，`buf[start]` 为当前字节
+    start: usize,
+    end: usize,
+    /// 当前字节中下一个要读取的位
+    bit: u8,
+    space: u8,
+    /// 内层已读完
+    eof: bool,
+    /// 已读出的位数
+    position: u64,
+    order: BitOrder,
+}
+
+impl<R: Read> BitReader<R> {
+    /// 按高位在前的位序读取
+    pub fn new(inner: R, space: u8) -> Self {
+        Self::with_order(inner, space, BitOrder::MsbFirst)
+    }
+
+    pub fn with_order(inner: R, space: u8, order: BitOrder) -> Self {
+        Self { inner, buf: vec![0; BUF_SIZE], start: 0, end: 0, bit: 0, space, eof: false, position: 0, order }
+    }
+
+    /// 已读出的位数，不含补位
+    pub fn position(&self) -> u64 {
+        self.position
+    }
+
+    /// 读取一位，数据读完时返回 `None`
+    ///
+    /// 最后一个字节的补位不全是 0，或没有数据却有补位时返回 `InvalidPadding`
+    pub fn read_bit(&mut self) -> Result<Option<bool>, HuffmanError> {
+        if self.end - self.start <= 1 && !self.eof {
+            self.fill()?;
+        }
+        if self.start == self.end {
+            return if self.position == 0 && self.space != 0 { Err(HuffmanError::InvalidPadding) } else { Ok(None) };
+        }
+        let byte = self.buf[self.start];
+        let last = self.eof && self.end - self.start == 1;
+        if last && self.bit == 0 && (self.space >= 8 || byte & self.order.tail_mask(8 - self.space) != 0) {
+            return Err(HuffmanError::InvalidPadding);
+        }
+        if last && self.bit >= 8 - self.space {
+            return Ok(None);
+        }
+        let bit = byte & self.order.mask(self.bit) != 0;
+        self.bit += 1;
+        self.position += 1;
+        if self.bit == 8 {
+            self.start += 1;
+            self.bit = 0;
+        }
+        Ok(Some(bit))
+    }
+
+    /// 结束读取，当前字节剩余的位只能是补位 0，之后也不能再有数据，否则返回 `InvalidPadding`
+    ///
+    /// 用于补位数量未记录、由读取的位数推出的数据
+    pub fn finish(mut self) -> Result<(), HuffmanError> {
+        if self.bit != 0 {
+            if self.buf[self.start] & self.order.tail_mask(self.bit) != 0 {
+                return Err(HuffmanError::InvalidPadding);
+            }
+            self.start += 1;
+        }
+        if self.start == self.end && !self.eof {
+            self.fill()?;
+        }
+        if self.start != self.end {
+            return Err(HuffmanError::InvalidPadding);
+        }
+        Ok(())
+    }
+
+    /// 把未读完的字节移到缓冲区开头并读入更多数据，读到末尾时设置 `eof`
+    fn fill(&mut self) -> io::Result<()> {
+        self.buf.copy_within(self.start..self.end, 0);
+        self.end -= self.start;
+        self.start = 0;
+        while self.end < self.buf.len() {
+            let n = read_some(&mut self.inner, &mut self.buf[self.end..])?;
+            if n == 0 {
+                self.eof = true;
+                break;
+            }
+            self.end += n;
+            // 有了当前字节之后的数据即可判断当前字节不是最后一个
+            if self.end > 1 {
+                break;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// 读取数据，被信号打断时重试
+#[cfg(feature = "std")]
+pub(crate) fn read_some<R: Read>(reader: &mut R, buf: &mut [u8]) -> io::Result<usize> {
+    loop {
+        match reader.read(buf) {
+            Err(e) if e.kind() == ErrorKind::Interrupted => continue,
+            result => return result,
+        }
+    }
+}
+
+#[cfg(not(feature = "std"))]
+fn read_some<R: Read>(reader: &mut R, buf: &mut [u8]) -> io::Result<usize> {
+    reader.read(buf)
+}
+
+/// 关闭 `std` 特性时代替 `std::io` 的最小读写接口，只为内存中的字节数组实现
+#[cfg(not(feature = "std"))]
+pub mod io {
+    use alloc::vec::Vec;
+
+    /// 内存读写不会失败，因此没有任何取值
+    #[derive(Debug)]
+    pub enum Error {}
+
+    pub type Result<T> = core::result::Result<T, Error>;
+
+    pub trait Write {
+        fn write_all(&mut self, buf: &[u8]) -> Result<()>;
+        fn flush(&mut self) -> Result<()> {
+            Ok(())
+        }
+    }
+
+    pub trait Read {
+        fn read(&mut self, buf: &mut [u8]) -> Result<usize>;
+    }
+
+    impl Write for Vec<u8> {
+        fn write_all(&mut self, buf: &[u8]) -> Result<()> {
+            self.extend_from_slice(buf);
+            Ok(())
+        }
+    }
+
+    impl<W: Write + ?Sized> Write for &mut W {
+        fn write_all(&mut self, buf: &[u8]) -> Result<()> {
+            (**self).write_all(buf)
+        }
+        fn flush(&mut self) -> Result<()> {
+            (**self).flush()
+        }
+    }
+
+    impl Read for &[u8] {
+        fn read(&mut self, buf: &mut [u8]) -> Result<usize> {
+            let n = buf.len().min(self.len());
+            let (head, tail) = self.split_at(n);
+            buf[..n].copy_from_slice(head);
+            *self = tail;
+            Ok(n)
+        }
+    }
+
+    impl<R: Read + ?Sized> Read for &mut R {
+        fn read(&mut self, buf: &mut [u8]) -> Result<usize> {
+            (**self).read(buf)
+        }
+    }
+
+    impl From<Error> for crate::HuffmanError {
+        fn from(e: Error) -> Self {
+            match e {}
+        }
+    }
+}
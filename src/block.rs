@@ -0,0 +1,139 @@
+//! 分块压缩，各块独立编码，可由多个线程并行压缩与解压
+//!
+//! 原文按固定大小切成若干块，每块由 [`ByteHuffmanCodec::encode_bytes`] 压缩成一个完整的压缩文件，
+//! 各自带有编码表与校验值。外层文件头设置 `FLAG_BLOCKS`，配置为 `capacity:原文长度` 与 `blocks:块数`，
+//! 配置之后是块索引（每块压缩后的长度，u64 小端），再依次是各块。
+//! 由块索引可以直接找到任意一块，不必解压之前的块。
+
+use std::{
+    convert::TryFrom,
+    io::Read,
+    sync::atomic::{AtomicUsize, Ordering},
+    sync::Mutex,
+};
+
+use crate::bytes::ByteHuffmanCodec;
+use crate::container::{read_u64, ContainerHeader, FLAG_BLOCKS, FLAG_BYTES};
+use crate::crc32::crc32;
+use crate::huffman::HuffmanError;
+
+impl ByteHuffmanCodec {
+    /// 把 `source` 按 `block_size` 字节切块，由 `threads` 个线程并行压缩
+    ///
+    /// 输出只与 `block_size` 有关，与线程数无关
+    pub fn encode_blocks(source: &[u8], block_size: usize, threads: usize) -> Result<Vec<u8>, HuffmanError> {
+        let blocks = source.chunks(block_size.max(1)).collect::<Vec<_>>();
+        let blocks = parallel_map(&blocks, threads, |block| Self::encode_bytes(block))?;
+        let config = format!("capacity:{}\nblocks:{}\n", source.len(), blocks.len());
+        let header = ContainerHeader::new(crc32(source), config).with_flags(FLAG_BYTES | FLAG_BLOCKS);
+        let size = header.size() + blocks.iter().map(|block| 8 + block.len()).sum::<usize>();
+        let mut result = Vec::with_capacity(size);
+        header.write(&mut result)?;
+        for block in &blocks {
+            result.extend_from_slice(&(block.len() as u64).to_le_bytes());
+        }
+        blocks.iter().for_each(|block| result.extend_from_slice(block));
+        Ok(result)
+    }
+
+    /// 由 `threads` 个线程并行解压 [`encode_blocks`](Self::encode_blocks) 生成的压缩文件
+    ///
+    /// 不分块的压缩文件同样可以解压，此时只用一个线程
+    pub fn decode_blocks(archive: &[u8], threads: usize) -> Result<Vec<u8>, HuffmanError> {
+        let mut reader = archive;
+        let header = ContainerHeader::read(&mut reader)?;
+        if !header.is_blocks() {
+            return Self::decode_bytes(archive);
+        }
+        let (capacity, index) = read_block_index(&header, &mut reader)?;
+        let mut blocks = vec![];
+        for len in index {
+            let len = usize::try_from(len).map_err(|_| HuffmanError::UnexpectedEof)?;
+            blocks.push(reader.get(..len).ok_or(HuffmanError::UnexpectedEof)?);
+            reader = &reader[len..];
+        }
+        let result = parallel_map(&blocks, threads, |block| decode_block(block))?.concat();
+        if result.len() as u64 != capacity {
+            return Err(HuffmanError::MalformedConfig(format!("capacity:{}", capacity)));
+        }
+        header.verify(crc32(&result))?;
+        Ok(result)
+    }
+
+    /// 数据开头是否为 [`encode_blocks`](Self::encode_blocks) 生成的分块压缩文件，只需要文件头的前几个字节
+    pub fn is_block_archive(bytes: &[u8]) -> bool {
+        ContainerHeader::peek_flag(bytes, FLAG_BLOCKS)
+    }
+}
+
+/// 解析分块压缩文件的配置并读取块索引，返回原文长度与每块压缩后的长度
+pub(crate) fn read_block_index<R: Read>(
+    header: &ContainerHeader,
+    reader: &mut R,
+) -> Result<(u64, Vec<u64>), HuffmanError> {
+    let (mut capacity, mut count) = (None, None);
+    for line in header.config.lines().filter(|line| !line.is_empty()) {
+        let malformed = || HuffmanError::MalformedConfig(line.to_owned());
+        match line.split_once(':').ok_or_else(malformed)? {
+            ("capacity", n) => capacity = Some(n.parse::<u64>().map_err(|_| malformed())?),
+            ("blocks", n) => count = Some(n.parse::<u64>().map_err(|_| malformed())?),
+            _ => return Err(malformed()),
+        }
+    }
+    let missing = |key: &str| HuffmanError::MalformedConfig(format!("分块配置中缺少 {}", key));
+    let capacity = capacity.ok_or_else(|| missing("capacity"))?;
+    let count = count.ok_or_else(|| missing("blocks"))?;
+    // 块数来自文件，不预先按其分配内存，索引不完整时在读取中途返回 `UnexpectedEof`
+    let mut index = vec![];
+    for _ in 0..count {
+        index.push(read_u64(reader)?);
+    }
+    Ok((capacity, index))
+}
+
+/// 读取一块压缩数据并解压
+pub(crate) fn read_block<R: Read>(reader: &mut R, len: u64) -> Result<Vec<u8>, HuffmanError> {
+    let mut block = vec![];
+    let read = reader.take(len).read_to_end(&mut block)?;
+    if (read as u64) < len {
+        return Err(HuffmanError::UnexpectedEof);
+    }
+    decode_block(&block)
+}
+
+/// 解压一块，块本身又是分块压缩文件时返回 `MalformedConfig`
+///
+/// 各块由 [`ByteHuffmanCodec::encode_bytes`] 生成，不会再分块；层层嵌套的恶意输入会在递归解压时耗尽栈
+fn decode_block(block: &[u8]) -> Result<Vec<u8>, HuffmanError> {
+    if ContainerHeader::peek_flag(block, FLAG_BLOCKS) {
+        return Err(HuffmanError::MalformedConfig("块中嵌套了分块压缩文件".to_owned()));
+    }
+    ByteHuffmanCodec::decode_bytes(block)
+}
+
+/// 用 `threads` 个线程对每一项执行 `f`，结果按原顺序返回，任意一项出错即返回该错误
+fn parallel_map<T, R, F>(items: &[T], threads: usize, f: F) -> Result<Vec<R>, HuffmanError>
+where
+    T: Sync,
+    R: Send,
+    F: Fn(&T) -> Result<R, HuffmanError> + Sync,
+{
+    let threads = threads.clamp(1, items.len().max(1));
+    if threads == 1 {
+        return items.iter().map(f).collect();
+    }
+    // 各线程依次领取下一项，处理得快的线程多领
+    let next = AtomicUsize::new(0);
+    let results = Mutex::new((0..items.len()).map(|_| None).collect::<Vec<_>>());
+    std::thread::scope(|scope| {
+        for _ in 0..threads {
+            scope.spawn(|| loop {
+                let i = next.fetch_add(1, Ordering::Relaxed);
+                let Some(item) = items.get(i) else { break };
+                let result = f(item);
+                results.lock().unwrap()[i] = Some(result);
+            });
+        }
+    });
+    results.into_inner().unwrap().into_iter().map(|result| result.unwrap()).collect()
+}
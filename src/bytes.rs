@@ -0,0 +1,111 @@
+//! 以字节为符号的哈夫曼编码，可压缩任意二进制数据
+
+use std::convert::TryFrom;
+
+use crate::container::{symbols_to_bytes, ContainerHeader, HfmArchive, FLAG_BYTES, FLAG_RLE};
+use crate::crc32::crc32;
+use crate::huffman::{DecodeConfig, HuffmanCodec, HuffmanError};
+use crate::rle;
+
+/// 压缩任意字节数据，输出单个自包含的压缩文件内容
+///
+/// 即 [`ByteHuffmanCodec::encode_bytes`]；写入内存不会出错，字节数也不会超出权重范围，因此不返回 `Result`
+pub fn compress(source: &[u8]) -> Vec<u8> {
+    ByteHuffmanCodec::encode_bytes(source).expect("压缩到内存不会失败")
+}
+
+/// 解压 [`compress`] 或 [`HuffmanCodec::encode_to_container`] 生成的压缩文件内容，即 [`ByteHuffmanCodec::decode_bytes`]
+pub fn decompress(archive: &[u8]) -> Result<Vec<u8>, HuffmanError> {
+    ByteHuffmanCodec::decode_bytes(archive)
+}
+
+/// 压缩任意字节数据，分别返回压缩数据与配置，不生成压缩文件头，也不经过任何文件
+///
+/// 即按字节编码的 [`HuffmanCodec::encode`]，配置为 UTF-8 文本；需要单个压缩文件时使用 [`compress`]
+pub fn compress_bytes(source: &[u8]) -> (Vec<u8>, Vec<u8>) {
+    let symbols = source.iter().map(|b| char::from(*b)).collect::<String>();
+    let (payload, config) = HuffmanCodec::encode(&symbols).expect("压缩到内存不会失败");
+    (payload, config.into_bytes())
+}
+
+/// 解压 [`compress_bytes`] 返回的压缩数据与配置
+///
+/// 两者位置颠倒时返回 `SwappedInputs`，而不是解出错误的内容
+pub fn decompress_bytes(payload: &[u8], config: &[u8]) -> Result<Vec<u8>, HuffmanError> {
+    if HuffmanCodec::is_config(payload) && !HuffmanCodec::is_config(config) {
+        return Err(HuffmanError::SwappedInputs);
+    }
+    let config = std::str::from_utf8(config)
+        .map_err(|_| HuffmanError::MalformedConfig("配置不是有效的 UTF-8 文本".to_owned()))?;
+    symbols_to_bytes(&HuffmanCodec::decode(payload, &DecodeConfig::build(config)?)?)
+}
+
+/// 字节哈夫曼编解码器
+///
+/// 把每个字节看作码点为其字节值的字符（`U+0000` ~ `U+00FF`）进行编码，
+/// 字母表最多只有 256 个符号，输出与 [`HuffmanCodec::encode_to_container`] 相同格式的压缩文件，
+/// 并设置标志位表示原文是字节
+pub struct ByteHuffmanCodec;
+
+impl ByteHuffmanCodec {
+    /// 压缩任意字节数据，输出单个自包含的压缩文件内容
+    ///
+    /// 与 [`HuffmanCodec::encode_to_container`] 一样，压缩后不比原文小时原样保存
+    pub fn encode_bytes(source: &[u8]) -> Result<Vec<u8>, HuffmanError> {
+        Self::encode_bytes_with_flags(source, 0)
+    }
+
+    /// 压缩字节数据，并在文件头中额外设置 `flags`
+    pub(crate) fn encode_bytes_with_flags(source: &[u8], flags: u8) -> Result<Vec<u8>, HuffmanError> {
+        let checksum = crc32(source);
+        let symbols = source.iter().map(|b| char::from(*b)).collect::<String>();
+        let (payload, config) = HuffmanCodec::encode(&symbols)?;
+        let (header, data) = if config.len() + payload.len() < source.len() {
+            (ContainerHeader::new(checksum, config), payload.as_slice())
+        } else {
+            (ContainerHeader::stored(checksum, source.len() as u64), source)
+        };
+        let header = header.with_flags(FLAG_BYTES | flags);
+        let mut result = Vec::with_capacity(header.size() + data.len());
+        header.write(&mut result)?;
+        result.extend_from_slice(data);
+        Ok(result)
+    }
+
+    /// 先对原文做游程编码再压缩，适合连续重复的字节较多的数据，如图像与日志
+    ///
+    /// 同时按 [`encode_bytes`](Self::encode_bytes) 压缩并比较，游程编码不能使结果更小时返回后者，
+    /// 因此结果不会比 `encode_bytes` 大；两种结果都由 [`decode_bytes`](Self::decode_bytes) 解压
+    pub fn encode_rle_then_huffman(source: &[u8]) -> Result<Vec<u8>, HuffmanError> {
+        let plain = Self::encode_bytes(source)?;
+        let runs = rle::encode(source);
+        // 游程编码后没有变短时，哈夫曼编码的结果也不会更小
+        if runs.len() >= source.len() {
+            return Ok(plain);
+        }
+        let symbols = runs.iter().map(|b| char::from(*b)).collect::<String>();
+        let (payload, config) = HuffmanCodec::encode(&symbols)?;
+        let header = ContainerHeader::new(crc32(&runs), config).with_flags(FLAG_BYTES | FLAG_RLE);
+        if header.size() + payload.len() >= plain.len() {
+            return Ok(plain);
+        }
+        let mut result = Vec::with_capacity(header.size() + payload.len());
+        header.write(&mut result)?;
+        result.extend_from_slice(&payload);
+        Ok(result)
+    }
+
+    /// 解压压缩文件内容并要求结果是 UTF-8 文本，不是时返回 `InvalidUtf8`
+    ///
+    /// 字节压缩文件的原文可以是任意字节，需要文本时使用本方法，而不是自行转换 [`decode_bytes`](Self::decode_bytes) 的结果
+    pub fn decode_to_string(archive: &[u8]) -> Result<String, HuffmanError> {
+        String::from_utf8(Self::decode_bytes(archive)?).map_err(|_| HuffmanError::InvalidUtf8)
+    }
+
+    /// 解压由 [`encode_bytes`](Self::encode_bytes) 生成的压缩文件内容
+    ///
+    /// 也能解压 [`HuffmanCodec::encode_to_container`] 生成的文本压缩文件，得到原文的 UTF-8 字节
+    pub fn decode_bytes(bytes: &[u8]) -> Result<Vec<u8>, HuffmanError> {
+        HfmArchive::try_from(bytes)?.decode()
+    }
+}
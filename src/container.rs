@@ -0,0 +1,609 @@
+//! 自包含的压缩文件格式
+//!
+//! 格式为：魔数 `HFM1` + 版本号（u8）+ 标志位（u8）+ 元数据（可选）+ 原文 CRC32（u32 小端）
+//! + 配置长度（u32 小端）+ 配置文本 + 压缩数据。
+//!
+//! 版本 1 的文件没有 CRC32 一项；版本 3 起配置中记录字符权重而非编码；版本 4 起增加标志位；
+//! 版本 5 起增加 [`FLAG_BYTES`]；版本 6 起增加 [`FLAG_DIR`]；版本 7 起增加 [`FLAG_BLOCKS`]；
+//! 版本 8 起增加 [`FLAG_LSB_FIRST`]；版本 9 起增加 [`FLAG_FOOTER`]；版本 10 起增加 [`FLAG_META`]；
+//! 版本 11 起增加 [`FLAG_RLE`]。
+//! 设置了 [`FLAG_STORED`] 时数据未经压缩，原样保存，配置中只记录原文长度；
+//! 否则配置中必须有 `count` 一项，解码恰好解出这么多字符后停止，压缩数据之后多出的内容一概忽略。
+//! 配置中有 `escape` 一项时，解码到该字符表示其后 21 位是编码表之外的字符的码点。
+//! 设置了 [`FLAG_BYTES`] 时原文是任意字节，编码表中的字符 `U+0000` ~ `U+00FF` 即对应的字节值。
+//! 设置了 [`FLAG_DIR`] 时原文是打包后的整个目录，格式见 `dir` 模块。
+//! 设置了 [`FLAG_BLOCKS`] 时原文分成多块各自压缩，格式见 `block` 模块。
+//! 设置了 [`FLAG_LSB_FIRST`] 时压缩数据按低位在前的位序打包，否则为高位在前。
+//! 设置了 [`FLAG_FOOTER`] 时文件头只有魔数、版本号与标志位，CRC32、配置长度与配置文本移到压缩数据之后，
+//! 最后 8 字节为这部分在文件中的偏移（u64 小端），此时压缩数据之后不能再有其他内容。
+//! 设置了 [`FLAG_META`] 时标志位之后是 [`ContainerMeta`]：一个字节记录有哪些字段（1 为文件名，2 为修改时间），
+//! 随后依次为文件名长度（u32 小端）+ 文件名与修改时间（u64 小端），没有的字段不占空间。
+//! 设置了 [`FLAG_RLE`] 时压缩数据解码后还需还原游程编码（格式见 `rle` 模块）才是原文，
+//! 此时 CRC32 为游程编码后的数据的校验值。
+
+use std::convert::TryFrom;
+use std::io::{ErrorKind, Read, Write};
+
+use crate::bytes::ByteHuffmanCodec;
+use crate::crc32::crc32;
+use crate::bits::{padding_bits, BitOrder};
+use crate::huffman::{CharWeightMap, DecodeConfig, HuffmanBinaryMap, HuffmanCodec, HuffmanError, HuffmanTree};
+use crate::rle;
+use crate::table::CodeTable;
+
+/// 压缩文件的魔数
+const MAGIC: &[u8; 4] = b"HFM1";
+/// 压缩文件格式版本
+const VERSION: u8 = 11;
+
+/// 标志位：数据原样保存，未经压缩
+pub(crate) const FLAG_STORED: u8 = 1;
+/// 标志位：原文是任意字节而非 UTF-8 文本，由 [`ByteHuffmanCodec`](crate::ByteHuffmanCodec) 生成
+pub(crate) const FLAG_BYTES: u8 = 2;
+/// 标志位：原文是打包后的目录，总是与 [`FLAG_BYTES`] 一起设置
+pub(crate) const FLAG_DIR: u8 = 4;
+/// 标志位：原文分块压缩，总是与 [`FLAG_BYTES`] 一起设置
+pub(crate) const FLAG_BLOCKS: u8 = 8;
+/// 标志位：压缩数据按低位在前的位序打包，见 [`BitOrder`]
+pub(crate) const FLAG_LSB_FIRST: u8 = 16;
+/// 标志位：CRC32 与配置写在压缩数据之后，见模块文档
+pub(crate) const FLAG_FOOTER: u8 = 32;
+/// 标志位：文件头中记录了 [`ContainerMeta`]
+pub(crate) const FLAG_META: u8 = 64;
+/// 标志位：原文先经过游程编码再做哈夫曼编码，总是与 [`FLAG_BYTES`] 一起设置
+pub(crate) const FLAG_RLE: u8 = 128;
+/// 文件头中标志位的偏移：魔数 + 版本号
+const FLAGS_OFFSET: usize = 5;
+
+/// 压缩文件中可选记录的原文件信息，类似 gzip 文件头中的文件名与修改时间
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ContainerMeta {
+    /// 原文件名，不含目录
+    pub filename: Option<String>,
+    /// 原文件的修改时间，自 UNIX 纪元起的秒数
+    pub mtime: Option<u64>,
+}
+
+impl ContainerMeta {
+    pub fn is_empty(&self) -> bool {
+        self.filename.is_none() && self.mtime.is_none()
+    }
+
+    /// 写入文件头后占用的字节数
+    fn size(&self) -> usize {
+        1 + self.filename.as_ref().map_or(0, |name| 4 + name.len()) + self.mtime.map_or(0, |_| 8)
+    }
+
+    fn write<W: Write>(&self, writer: &mut W) -> Result<(), HuffmanError> {
+        let fields = self.filename.is_some() as u8 | (self.mtime.is_some() as u8) << 1;
+        writer.write_all(&[fields])?;
+        if let Some(name) = &self.filename {
+            writer.write_all(&(name.len() as u32).to_le_bytes())?;
+            writer.write_all(name.as_bytes())?;
+        }
+        if let Some(mtime) = self.mtime {
+            writer.write_all(&mtime.to_le_bytes())?;
+        }
+        Ok(())
+    }
+
+    fn read<R: Read>(reader: &mut R) -> Result<Self, HuffmanError> {
+        let fields = read_u8(reader)?;
+        if fields & !3 != 0 {
+            return Err(HuffmanError::MalformedConfig(format!("未知的元数据字段：{:02X}", fields)));
+        }
+        let filename = if fields & 1 != 0 {
+            let name = read_sized(reader)?;
+            Some(String::from_utf8(name).map_err(|_| HuffmanError::MalformedConfig("文件名不是有效的 UTF-8 文本".to_owned()))?)
+        } else {
+            None
+        };
+        let mtime = if fields & 2 != 0 { Some(read_u64(reader)?) } else { None };
+        Ok(Self { filename, mtime })
+    }
+}
+
+/// 压缩文件头
+pub(crate) struct ContainerHeader {
+    /// 文件格式版本
+    pub version: u8,
+    /// 标志位
+    pub flags: u8,
+    /// 原文的 CRC32 校验值
+    pub checksum: u32,
+    /// 配置文本
+    pub config: String,
+    /// 原文件信息，未设置 [`FLAG_META`] 时为空
+    pub meta: ContainerMeta,
+}
+
+impl ContainerHeader {
+    pub fn new(checksum: u32, config: String) -> Self {
+        Self { version: VERSION, flags: 0, checksum, config, meta: ContainerMeta::default() }
+    }
+
+    /// 原样保存 `len` 字节数据时使用的文件头
+    pub fn stored(checksum: u32, len: u64) -> Self {
+        Self::new(checksum, format!("capacity:{}\n", len)).with_flags(FLAG_STORED)
+    }
+
+    /// 追加标志位
+    pub fn with_flags(mut self, flags: u8) -> Self {
+        self.flags |= flags;
+        self
+    }
+
+    /// 记录原文件信息，`meta` 为空时不占用任何空间
+    pub fn with_meta(mut self, meta: ContainerMeta) -> Self {
+        if !meta.is_empty() {
+            self.flags |= FLAG_META;
+        }
+        self.meta = meta;
+        self
+    }
+
+    pub fn is_stored(&self) -> bool {
+        self.flags & FLAG_STORED != 0
+    }
+
+    pub fn is_bytes(&self) -> bool {
+        self.flags & FLAG_BYTES != 0
+    }
+
+    pub fn is_dir(&self) -> bool {
+        self.flags & FLAG_DIR != 0
+    }
+
+    pub fn is_blocks(&self) -> bool {
+        self.flags & FLAG_BLOCKS != 0
+    }
+
+    pub fn is_footer(&self) -> bool {
+        self.flags & FLAG_FOOTER != 0
+    }
+
+    pub fn is_rle(&self) -> bool {
+        self.flags & FLAG_RLE != 0
+    }
+
+    /// 只查看数据开头，判断是否为设置了 `flag` 的压缩文件，不解析完整的文件头
+    pub fn peek_flag(bytes: &[u8], flag: u8) -> bool {
+        bytes.starts_with(MAGIC)
+            && bytes.get(MAGIC.len()).is_some_and(|version| *version >= 4)
+            && bytes.get(FLAGS_OFFSET).is_some_and(|flags| flags & flag != 0)
+    }
+
+    /// 文件头在数据部分之前的总字节数，按版本号计算：版本 4 之前没有标志位，版本 1 没有 CRC32
+    pub fn size(&self) -> usize {
+        let flags = if self.version >= 4 { 1 } else { 0 };
+        let meta = if self.version >= 10 && self.flags & FLAG_META != 0 { self.meta.size() } else { 0 };
+        let prefix = MAGIC.len() + 1 + flags + meta;
+        if self.version >= 9 && self.is_footer() {
+            return prefix;
+        }
+        let checksum = if self.version >= 2 { 4 } else { 0 };
+        prefix + checksum + 4 + self.config.len()
+    }
+
+    /// 写入压缩文件头，设置了 [`FLAG_FOOTER`] 时只写入魔数、版本号、标志位与元数据
+    pub fn write<W: Write>(&self, writer: &mut W) -> Result<(), HuffmanError> {
+        writer.write_all(MAGIC)?;
+        writer.write_all(&[self.version, self.flags])?;
+        if self.flags & FLAG_META != 0 {
+            self.meta.write(writer)?;
+        }
+        if !self.is_footer() {
+            self.write_config(writer)?;
+        }
+        Ok(())
+    }
+
+    /// 在压缩数据之后写入 CRC32 与配置，`offset` 为写入位置在文件中的偏移
+    pub fn write_footer<W: Write>(&self, writer: &mut W, offset: u64) -> Result<(), HuffmanError> {
+        self.write_config(writer)?;
+        writer.write_all(&offset.to_le_bytes())?;
+        Ok(())
+    }
+
+    fn write_config<W: Write>(&self, writer: &mut W) -> Result<(), HuffmanError> {
+        writer.write_all(&self.checksum.to_le_bytes())?;
+        writer.write_all(&(self.config.len() as u32).to_le_bytes())?;
+        writer.write_all(self.config.as_bytes())?;
+        Ok(())
+    }
+
+    /// 读取并校验压缩文件头
+    pub fn read<R: Read>(reader: &mut R) -> Result<Self, HuffmanError> {
+        let mut magic = [0u8; 4];
+        match reader.read_exact(&mut magic) {
+            Ok(()) if &magic == MAGIC => (),
+            Ok(()) => return Err(HuffmanError::InvalidMagic),
+            Err(e) if e.kind() == ErrorKind::UnexpectedEof => return Err(HuffmanError::InvalidMagic),
+            Err(e) => return Err(e.into()),
+        }
+        let version = read_u8(reader)?;
+        if version == 0 || version > VERSION {
+            return Err(HuffmanError::UnsupportedVersion(version));
+        }
+        let flags = if version >= 4 { read_u8(reader)? } else { 0 };
+        let meta = if version >= 10 && flags & FLAG_META != 0 { ContainerMeta::read(reader)? } else { ContainerMeta::default() };
+        if version >= 9 && flags & FLAG_FOOTER != 0 {
+            // CRC32 与配置在文件末尾，由 `parse` 读取
+            return Ok(Self { version, flags, checksum: 0, config: String::new(), meta });
+        }
+        let checksum = if version >= 2 { read_u32(reader)? } else { 0 };
+        let config = read_config(reader)?;
+        Ok(Self { version, flags, checksum, config, meta })
+    }
+
+    /// 解析内存中完整的压缩文件，返回文件头与其后的数据部分
+    ///
+    /// 配置在文件末尾时先按最后 8 字节的偏移读出 CRC32 与配置，数据部分不含末尾的这些内容
+    pub fn parse(bytes: &[u8]) -> Result<(Self, &[u8]), HuffmanError> {
+        let mut reader = bytes;
+        let mut header = Self::read(&mut reader)?;
+        if !header.is_footer() {
+            return Ok((header, reader));
+        }
+        let end = bytes.len().checked_sub(8).filter(|end| *end >= header.size()).ok_or(HuffmanError::UnexpectedEof)?;
+        let offset = usize::try_from(read_u64(&mut &bytes[end..])?)
+            .ok()
+            .filter(|offset| (header.size()..=end).contains(offset))
+            .ok_or_else(|| HuffmanError::MalformedConfig("配置的偏移超出文件范围".to_owned()))?;
+        let mut footer = &bytes[offset..end];
+        header.checksum = read_u32(&mut footer)?;
+        header.config = read_config(&mut footer)?;
+        if !footer.is_empty() {
+            return Err(HuffmanError::MalformedConfig("配置之后有多余的数据".to_owned()));
+        }
+        let data = &bytes[header.size()..offset];
+        Ok((header, data))
+    }
+
+    /// 解析压缩数据的配置，配置引用共享编码表时使用 `shared`
+    ///
+    /// 压缩文件的配置总是记录字符数量，缺少时返回 `MalformedConfig`，不退回到只靠补位判断数据结尾
+    pub fn decode_config(&self, shared: Option<&CodeTable>) -> Result<DecodeConfig, HuffmanError> {
+        let mut decode_map = DecodeConfig::build_with_table(&self.config, shared)?;
+        if !self.is_stored() && decode_map.count.is_none() {
+            return Err(HuffmanError::MalformedConfig("配置中缺少字符数量 count".to_owned()));
+        }
+        if self.flags & FLAG_LSB_FIRST != 0 {
+            decode_map.order = BitOrder::LsbFirst;
+        }
+        Ok(decode_map)
+    }
+
+    /// 校验解压结果的 CRC32，版本 1 的文件不做校验
+    pub fn verify(&self, checksum: u32) -> Result<(), HuffmanError> {
+        if self.version >= 2 && checksum != self.checksum {
+            return Err(HuffmanError::ChecksumMismatch);
+        }
+        Ok(())
+    }
+}
+
+/// 已校验过文件头的压缩文件，由 `HfmArchive::try_from(&bytes[..])` 解析得到
+///
+/// 解析时检查魔数与版本号，读出元数据并解析编码表，格式有误时立即返回相应的错误；
+/// 解压时再核对 CRC32。引用共享编码表的压缩文件需改用 [`HuffmanCodec::with_table`] 解压
+pub struct HfmArchive<'a> {
+    bytes: &'a [u8],
+    header: ContainerHeader,
+    /// 文件头之后的数据部分
+    data: &'a [u8],
+    /// 分块压缩文件的编码表在各块中，没有整体的配置
+    decode_map: Option<DecodeConfig>,
+}
+
+impl<'a> TryFrom<&'a [u8]> for HfmArchive<'a> {
+    type Error = HuffmanError;
+
+    fn try_from(bytes: &'a [u8]) -> Result<Self, HuffmanError> {
+        let (header, data) = ContainerHeader::parse(bytes)?;
+        let decode_map = if header.is_blocks() { None } else { Some(header.decode_config(None)?) };
+        Ok(Self { bytes, header, data, decode_map })
+    }
+}
+
+impl HfmArchive<'_> {
+    /// 文件格式版本
+    pub fn version(&self) -> u8 {
+        self.header.version
+    }
+
+    /// 文件头中记录的原文件信息
+    pub fn meta(&self) -> &ContainerMeta {
+        &self.header.meta
+    }
+
+    /// 原文是否为任意字节，否则为 UTF-8 文本
+    pub fn is_bytes(&self) -> bool {
+        self.header.is_bytes()
+    }
+
+    /// 是否为打包后的目录，见 [`ByteHuffmanCodec::decode_dir`]
+    pub fn is_dir(&self) -> bool {
+        self.header.is_dir()
+    }
+
+    /// 解压出原文的字节，文本压缩文件即原文的 UTF-8 编码
+    pub fn decode(&self) -> Result<Vec<u8>, HuffmanError> {
+        let decode_map = match &self.decode_map {
+            Some(decode_map) => decode_map,
+            None => return ByteHuffmanCodec::decode_blocks(self.bytes, 1),
+        };
+        let result = if self.header.is_stored() {
+            self.data.get(..decode_map.capacity).ok_or(HuffmanError::UnexpectedEof)?.to_vec()
+        } else if self.header.is_bytes() {
+            symbols_to_bytes(&HuffmanCodec::decode(self.data, decode_map)?)?
+        } else {
+            HuffmanCodec::decode(self.data, decode_map)?.into_bytes()
+        };
+        self.header.verify(crc32(&result))?;
+        if self.header.is_rle() {
+            return rle::decode(&result);
+        }
+        Ok(result)
+    }
+}
+
+/// 压缩比率，即压缩后大小占原大小的比例，原文为空时返回 0
+pub fn compression_ratio(original_len: usize, compressed_len: usize) -> f64 {
+    if original_len == 0 {
+        return 0.0;
+    }
+    compressed_len as f64 / original_len as f64
+}
+
+/// 压缩结果的大小统计
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct CompressionReport {
+    /// 原文字节数
+    pub original: usize,
+    /// 压缩文件总字节数，包含文件头与配置
+    pub compressed: usize,
+    /// 文件头与配置所占的字节数
+    pub config_size: usize,
+    /// 压缩比率，见 [`compression_ratio`]
+    pub ratio: f64,
+    /// 压缩数据中有效的位数，不含末尾补位；原样保存时为原文字节数的 8 倍
+    pub payload_bits: u64,
+}
+
+impl CompressionReport {
+    /// 有效位数默认按文件头与配置之后的全部字节计算，编码时由 [`with_payload_bits`](Self::with_payload_bits) 给出确切值
+    pub fn new(original: usize, compressed: usize, config_size: usize) -> Self {
+        let payload_bits = (compressed - config_size) as u64 * 8;
+        Self { original, compressed, config_size, ratio: compression_ratio(original, compressed), payload_bits }
+    }
+
+    pub fn with_payload_bits(mut self, payload_bits: u64) -> Self {
+        self.payload_bits = payload_bits;
+        self
+    }
+}
+
+impl HuffmanCodec {
+    /// 哈夫曼编码，输出单个自包含的压缩文件内容
+    ///
+    /// 压缩后反而不比原文小时（如随机数据），改为原样保存，文件至多比原文多出一个文件头
+    pub fn encode_to_container(source: &str) -> Result<Vec<u8>, HuffmanError> {
+        Ok(Self::encode_with_report(source)?.0)
+    }
+
+    /// 与 [`encode_to_container`](Self::encode_to_container) 相同，但压缩数据按 `order` 的位序打包
+    ///
+    /// 位序记录在文件头中，解压时自动识别
+    pub fn encode_to_container_with_order(source: &str, order: BitOrder) -> Result<Vec<u8>, HuffmanError> {
+        Ok(Self::encode_report_with_order(source, order, 0, ContainerMeta::default())?.0)
+    }
+
+    /// 与 [`encode_to_container`](Self::encode_to_container) 相同，但 CRC32 与配置写在压缩数据之后
+    ///
+    /// 便于先输出压缩数据、最后再写入元数据的场合，解压时自动识别
+    pub fn encode_to_container_with_footer(source: &str) -> Result<Vec<u8>, HuffmanError> {
+        Ok(Self::encode_report_with_order(source, BitOrder::MsbFirst, FLAG_FOOTER, ContainerMeta::default())?.0)
+    }
+
+    /// 与 [`encode_to_container`](Self::encode_to_container) 相同，并在文件头中记录原文件信息 `meta`
+    ///
+    /// 解压时可由 [`decode_with_meta`](Self::decode_with_meta) 或 [`read_meta`](Self::read_meta) 取回
+    pub fn encode_to_container_with_meta(source: &str, meta: ContainerMeta) -> Result<Vec<u8>, HuffmanError> {
+        Ok(Self::encode_report_with_order(source, BitOrder::MsbFirst, 0, meta)?.0)
+    }
+
+    /// 与 [`encode_to_container`](Self::encode_to_container) 相同，同时返回大小统计
+    pub fn encode_with_report(source: &str) -> Result<(Vec<u8>, CompressionReport), HuffmanError> {
+        Self::encode_report_with_order(source, BitOrder::MsbFirst, 0, ContainerMeta::default())
+    }
+
+    /// 按 `order` 的位序编码，文件头追加标志位 `flags` 并记录 `meta`
+    fn encode_report_with_order(
+        source: &str,
+        order: BitOrder,
+        flags: u8,
+        meta: ContainerMeta,
+    ) -> Result<(Vec<u8>, CompressionReport), HuffmanError> {
+        let checksum = crc32(source.as_bytes());
+        let (bit_map, config, total_bits) = Self::plan_container(&CharWeightMap::build(source), source.len())?;
+        let (payload, _) = Self::pack_with_order(source, &bit_map, order)?;
+        let (header, data, payload_bits) = if config.len() + payload.len() < source.len() {
+            let order_flag = if order == BitOrder::LsbFirst { FLAG_LSB_FIRST } else { 0 };
+            (ContainerHeader::new(checksum, config).with_flags(order_flag), payload.as_slice(), total_bits)
+        } else {
+            let stored_bits = source.len() as u64 * 8;
+            (ContainerHeader::stored(checksum, source.len() as u64), source.as_bytes(), stored_bits)
+        };
+        let header = header.with_flags(flags).with_meta(meta);
+        let mut result = Vec::with_capacity(header.size() + data.len());
+        header.write(&mut result)?;
+        result.extend_from_slice(data);
+        if header.is_footer() {
+            let offset = result.len() as u64;
+            header.write_footer(&mut result, offset)?;
+        }
+        let report = CompressionReport::new(source.len(), result.len(), result.len() - data.len())
+            .with_payload_bits(payload_bits);
+        Ok((result, report))
+    }
+
+    /// 与 [`encode`](Self::encode) 相同，但返回编码表本身而非配置文本，便于直接记录或复用每个字符的编码
+    ///
+    /// 压缩数据与 [`encode`](Self::encode) 完全相同，大小统计按压缩数据加上配置文本计算
+    pub fn encode_detailed(source: &str) -> Result<(Vec<u8>, CodeTable, CompressionReport), HuffmanError> {
+        let weights = CharWeightMap::build(source);
+        let table = CodeTable::from_weights(weights.clone())?;
+        let (payload, space) = Self::pack(source, table.bit_map())?;
+        let config = Self::config_text(space, source.len(), source.chars().count(), "weight", &weights);
+        let report = CompressionReport::new(source.len(), payload.len() + config.len(), config.len())
+            .with_payload_bits(payload.len() as u64 * 8 - u64::from(space));
+        Ok((payload, table, report))
+    }
+
+    /// 预估 [`encode_with_report`](Self::encode_with_report) 的大小统计，不生成压缩数据
+    ///
+    /// 只统计权重、建树并由编码长度算出总位数，结果与实际压缩完全一致，包括改为原样保存的情况
+    pub fn estimate(source: &str) -> Result<CompressionReport, HuffmanError> {
+        Self::estimate_with_weights(&CharWeightMap::build(source), source.len())
+    }
+
+    /// 由字符权重与原文字节数 `capacity` 预估大小统计
+    pub(crate) fn estimate_with_weights(weights: &CharWeightMap, capacity: usize) -> Result<CompressionReport, HuffmanError> {
+        let (_, config, total_bits) = Self::plan_container(weights, capacity)?;
+        let payload = total_bits.div_ceil(8) as usize;
+        let header = if config.len() + payload < capacity {
+            ContainerHeader::new(0, config)
+        } else {
+            ContainerHeader::stored(0, capacity as u64)
+        };
+        if header.is_stored() {
+            return Ok(CompressionReport::new(capacity, header.size() + capacity, header.size()));
+        }
+        Ok(CompressionReport::new(capacity, header.size() + payload, header.size()).with_payload_bits(total_bits))
+    }
+
+    /// 由字符权重建立编码表，返回编码表、压缩文件的配置与压缩数据的总位数
+    pub(crate) fn plan_container(
+        weights: &CharWeightMap,
+        capacity: usize,
+    ) -> Result<(HuffmanBinaryMap, String, u64), HuffmanError> {
+        let bit_map = HuffmanBinaryMap::build(&HuffmanTree::build(weights.clone())?)?;
+        let total_bits: u64 = weights.iter()
+            .map(|(ch, weight)| weight * bit_map.inner[ch].len() as u64)
+            .sum();
+        // 补位只与总位数的低 3 位有关，转换成 usize 时即使截断也不影响结果
+        let space = padding_bits(total_bits as usize);
+        let config = Self::config_text(space, capacity, weights.total()? as usize, "weight", weights);
+        Ok((bit_map, config, total_bits))
+    }
+
+    /// 解压 `archive` 并与原文逐字节比较，压缩文件损坏时返回错误
+    ///
+    /// 文本与字节压缩文件均可校验
+    pub fn verify(original: &[u8], archive: &[u8]) -> Result<bool, HuffmanError> {
+        Ok(ByteHuffmanCodec::decode_bytes(archive)? == original)
+    }
+
+    /// 数据开头是否为压缩文件的魔数，可用于判断文件是否由本库压缩
+    pub fn is_container(bytes: &[u8]) -> bool {
+        bytes.starts_with(MAGIC)
+    }
+
+    /// 数据开头是否像 [`encode`](Self::encode) 生成的配置文本，用于发现压缩数据与配置被颠倒
+    pub fn is_config(bytes: &[u8]) -> bool {
+        bytes.starts_with(b"space:") || bytes.starts_with(b"capacity:")
+    }
+
+    /// 解码由 `encode_to_container` 生成的压缩文件内容
+    pub fn decode_from_container(bytes: &[u8]) -> Result<String, HuffmanError> {
+        Self::decode_container_with_table(bytes, None)
+    }
+
+    /// 与 [`decode_from_container`](Self::decode_from_container) 相同，同时返回文件头中记录的原文件信息
+    pub fn decode_with_meta(bytes: &[u8]) -> Result<(String, ContainerMeta), HuffmanError> {
+        let meta = ContainerHeader::read(&mut &bytes[..])?.meta;
+        Ok((Self::decode_from_container(bytes)?, meta))
+    }
+
+    /// 只读取文件头中记录的原文件信息，不解压数据
+    ///
+    /// 没有记录时返回空的 [`ContainerMeta`]
+    pub fn read_meta<R: Read>(mut reader: R) -> Result<ContainerMeta, HuffmanError> {
+        Ok(ContainerHeader::read(&mut reader)?.meta)
+    }
+
+    /// 解码压缩文件内容，配置引用共享编码表时使用 `shared`
+    pub(crate) fn decode_container_with_table(bytes: &[u8], shared: Option<&CodeTable>) -> Result<String, HuffmanError> {
+        let (header, reader) = ContainerHeader::parse(bytes)?;
+        if header.is_blocks() || header.is_rle() {
+            let bytes = ByteHuffmanCodec::decode_blocks(bytes, 1)?;
+            return String::from_utf8(bytes).map_err(|_| HuffmanError::InvalidUtf8);
+        }
+        let decode_map = header.decode_config(shared)?;
+        let result = if header.is_stored() {
+            let data = reader.get(..decode_map.capacity).ok_or(HuffmanError::UnexpectedEof)?;
+            String::from_utf8(data.to_vec()).map_err(|_| HuffmanError::InvalidUtf8)?
+        } else if header.is_bytes() {
+            // 字节压缩文件的原文恰好是 UTF-8 文本时也能按文本解出
+            let bytes = symbols_to_bytes(&Self::decode(reader, &decode_map)?)?;
+            String::from_utf8(bytes).map_err(|_| HuffmanError::InvalidUtf8)?
+        } else {
+            Self::decode(reader, &decode_map)?
+        };
+        header.verify(crc32(result.as_bytes()))?;
+        Ok(result)
+    }
+}
+
+/// 把字节压缩文件解码出的字符还原成字节，每个字符的码点即字节值
+pub(crate) fn symbols_to_bytes(symbols: &str) -> Result<Vec<u8>, HuffmanError> {
+    symbols.chars()
+        .map(|ch| u8::try_from(ch).map_err(|_| HuffmanError::MalformedConfig(format!("U+{:04X}", ch as u32))))
+        .collect()
+}
+
+/// 读取配置长度与配置文本
+fn read_config<R: Read>(reader: &mut R) -> Result<String, HuffmanError> {
+    let config = read_sized(reader)?;
+    String::from_utf8(config).map_err(|_| HuffmanError::MalformedConfig("配置不是有效的 UTF-8 文本".to_owned()))
+}
+
+/// 读取 `u32` 长度前缀及其后的数据，数据不足时返回 `UnexpectedEof`
+///
+/// 按实际读到的数据分配内存，不按长度前缀预先分配，避免损坏的长度字段一次申请数 GB 内存
+pub(crate) fn read_sized<R: Read>(reader: &mut R) -> Result<Vec<u8>, HuffmanError> {
+    let len = u64::from(read_u32(reader)?);
+    let mut buf = vec![];
+    reader.take(len).read_to_end(&mut buf)?;
+    if (buf.len() as u64) < len {
+        return Err(HuffmanError::UnexpectedEof);
+    }
+    Ok(buf)
+}
+
+/// 读取指定长度的数据，数据不足时返回 `UnexpectedEof`
+pub(crate) fn read_exact<R: Read>(reader: &mut R, buf: &mut [u8]) -> Result<(), HuffmanError> {
+    reader.read_exact(buf).map_err(|e| match e.kind() {
+        ErrorKind::UnexpectedEof => HuffmanError::UnexpectedEof,
+        _ => e.into(),
+    })
+}
+
+pub(crate) fn read_u8<R: Read>(reader: &mut R) -> Result<u8, HuffmanError> {
+    let mut buf = [0u8; 1];
+    read_exact(reader, &mut buf)?;
+    Ok(buf[0])
+}
+
+pub(crate) fn read_u32<R: Read>(reader: &mut R) -> Result<u32, HuffmanError> {
+    let mut buf = [0u8; 4];
+    read_exact(reader, &mut buf)?;
+    Ok(u32::from_le_bytes(buf))
+}
+
+pub(crate) fn read_u64<R: Read>(reader: &mut R) -> Result<u64, HuffmanError> {
+    let mut buf = [0u8; 8];
+    read_exact(reader, &mut buf)?;
+    Ok(u64::from_le_bytes(buf))
+}
@@ -0,0 +1,46 @@
+//! CRC32（IEEE 802.3）校验，用于检测压缩文件是否损坏
+
+/// 按字节查表计算所用的表
+const TABLE: [u32; 256] = {
+    let mut table = [0u32; 256];
+    let mut i = 0;
+    while i < 256 {
+        let mut crc = i as u32;
+        let mut j = 0;
+        while j < 8 {
+            crc = if crc & 1 == 1 { (crc >> 1) ^ 0xEDB8_8320 } else { crc >> 1 };
+            j += 1;
+        }
+        table[i] = crc;
+        i += 1;
+    }
+    table
+};
+
+/// 可分块累计的 CRC32 计算器
+pub(crate) struct Crc32 {
+    value: u32,
+}
+
+impl Crc32 {
+    pub fn new() -> Self {
+        Self { value: !0 }
+    }
+
+    pub fn update(&mut self, bytes: &[u8]) {
+        for b in bytes {
+            self.value = TABLE[((self.value ^ *b as u32) & 0xFF) as usize] ^ (self.value >> 8);
+        }
+    }
+
+    pub fn finish(&self) -> u32 {
+        !self.value
+    }
+}
+
+/// 计算一段数据的 CRC32
+pub(crate) fn crc32(bytes: &[u8]) -> u32 {
+    let mut crc = Crc32::new();
+    crc.update(bytes);
+    crc.finish()
+}
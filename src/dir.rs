@@ -0,0 +1,108 @@
+//! 把整个目录压缩成一个文件
+//!
+//! 目录按文件名排序深度优先遍历，每一项依次写成：
+//! 类型（u8，0 为目录，1 为文件）+ 路径长度（u32 小端）+ 路径 + 内容长度（u64 小端）+ 内容。
+//! 路径为相对于根目录、以 `/` 分隔的 UTF-8 文本，目录的内容长度为 0。
+//! 拼接后的数据由 [`ByteHuffmanCodec`] 压缩，并在文件头中设置 `FLAG_DIR`。
+//!
+//! 打包时整个目录的内容都会读入内存；符号链接不会被跟随，也不会被打包。
+
+use std::{
+    fs,
+    io::{self, ErrorKind},
+    path::Path,
+};
+
+use crate::bytes::ByteHuffmanCodec;
+use crate::container::{read_sized, read_u64, read_u8, ContainerHeader, FLAG_DIR};
+use crate::huffman::HuffmanError;
+
+/// 目录项类型：目录
+const ENTRY_DIR: u8 = 0;
+/// 目录项类型：文件
+const ENTRY_FILE: u8 = 1;
+
+impl ByteHuffmanCodec {
+    /// 把目录 `dir` 下的所有子目录和文件压缩成单个压缩文件内容，空目录与空文件也会保留
+    pub fn encode_dir(dir: &Path) -> Result<Vec<u8>, HuffmanError> {
+        let mut bundle = vec![];
+        bundle_dir(dir, "", &mut bundle)?;
+        Self::encode_bytes_with_flags(&bundle, FLAG_DIR)
+    }
+
+    /// 把 [`encode_dir`](Self::encode_dir) 生成的压缩文件解压到目录 `dest` 下，
+    /// `dest` 不存在时自动创建，已存在的同名文件会被覆盖
+    ///
+    /// 路径为绝对路径或包含 `..` 等会写到 `dest` 之外的压缩文件返回 `InvalidPath`
+    pub fn decode_dir(archive: &[u8], dest: &Path) -> Result<(), HuffmanError> {
+        if !ContainerHeader::read(&mut &archive[..])?.is_dir() {
+            return Err(HuffmanError::NotDirectoryArchive);
+        }
+        let bundle = Self::decode_bytes(archive)?;
+        let mut reader = bundle.as_slice();
+        fs::create_dir_all(dest)?;
+        while !reader.is_empty() {
+            let kind = read_u8(&mut reader)?;
+            let path = String::from_utf8(read_sized(&mut reader)?)
+                .map_err(|e| HuffmanError::InvalidPath(String::from_utf8_lossy(e.as_bytes()).into_owned()))?;
+            let len = read_u64(&mut reader)? as usize;
+            let content = reader.get(..len).ok_or(HuffmanError::UnexpectedEof)?;
+            reader = &reader[len..];
+            let target = dest.join(safe_relative_path(&path)?);
+            match kind {
+                ENTRY_DIR => fs::create_dir_all(target)?,
+                ENTRY_FILE => {
+                    if let Some(parent) = target.parent() {
+                        fs::create_dir_all(parent)?;
+                    }
+                    fs::write(target, content)?;
+                },
+                _ => return Err(HuffmanError::MalformedConfig(format!("未知的目录项类型：{}", kind))),
+            }
+        }
+        Ok(())
+    }
+
+    /// 数据开头是否为 [`encode_dir`](Self::encode_dir) 生成的目录压缩文件，只需要文件头的前几个字节
+    pub fn is_dir_archive(bytes: &[u8]) -> bool {
+        ContainerHeader::peek_flag(bytes, FLAG_DIR)
+    }
+}
+
+/// 把目录 `dir` 下的内容追加到 `bundle`，`prefix` 为 `dir` 相对于根目录的路径
+fn bundle_dir(dir: &Path, prefix: &str, bundle: &mut Vec<u8>) -> Result<(), HuffmanError> {
+    let mut entries = fs::read_dir(dir)?.collect::<Result<Vec<_>, _>>()?;
+    entries.sort_by_key(|entry| entry.file_name());
+    for entry in entries {
+        let name = entry.file_name().into_string()
+            .map_err(|name| io::Error::new(ErrorKind::InvalidData, format!("路径不是有效的 UTF-8 文本：{:?}", name)))?;
+        let path = if prefix.is_empty() { name } else { format!("{}/{}", prefix, name) };
+        let file_type = entry.file_type()?;
+        if file_type.is_dir() {
+            push_entry(bundle, ENTRY_DIR, &path, &[]);
+            bundle_dir(&entry.path(), &path, bundle)?;
+        } else if file_type.is_file() {
+            push_entry(bundle, ENTRY_FILE, &path, &fs::read(entry.path())?);
+        }
+    }
+    Ok(())
+}
+
+fn push_entry(bundle: &mut Vec<u8>, kind: u8, path: &str, content: &[u8]) {
+    bundle.push(kind);
+    bundle.extend_from_slice(&(path.len() as u32).to_le_bytes());
+    bundle.extend_from_slice(path.as_bytes());
+    bundle.extend_from_slice(&(content.len() as u64).to_le_bytes());
+    bundle.extend_from_slice(content);
+}
+
+/// 检查压缩文件中的路径只会落在解压目录之内
+fn safe_relative_path(path: &str) -> Result<&Path, HuffmanError> {
+    let safe = !path.is_empty() && path.split('/').all(|part| {
+        !matches!(part, "" | "." | "..") && !part.contains(['\\', ':'])
+    });
+    if !safe {
+        return Err(HuffmanError::InvalidPath(path.to_owned()));
+    }
+    Ok(Path::new(path))
+}
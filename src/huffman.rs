@@ -1,18 +1,146 @@
-use std::{cell::RefCell, collections::{HashMap, hash_map::{Iter}}, fmt::{Display}, ops::AddAssign, rc::Rc, vec};
+use alloc::{
+    borrow::ToOwned,
+    collections::BinaryHeap,
+    format,
+    rc::Rc,
+    string::{String, ToString},
+    vec,
+    vec::Vec,
+};
+use core::{cell::RefCell, cmp::Reverse, convert::TryFrom, fmt::{self, Display}, hash::Hash, ops::AddAssign};
 
-type RefHuffmanTree = Rc<RefCell<HuffmanTree>>;
-type Weight = u64;
+use crate::bits::{padding_bits, BitOrder, BitReader, BitWriter};
+use crate::table::CodeTable;
+
+/// 关闭 `std` 特性时没有哈希表，改用 `BTreeMap` / `BTreeSet`，符号本就要求 `Ord`
+#[cfg(feature = "std")]
+pub(crate) use std::collections::{hash_map::Iter, HashMap as Map, HashSet as Set};
+#[cfg(not(feature = "std"))]
+pub(crate) use alloc::collections::{btree_map::Iter, BTreeMap as Map, BTreeSet as Set};
+
+/// 转义符之后直接记录的码点位数，足以表示 `U+10FFFF` 以内的所有字符
+pub(crate) const ESCAPE_BITS: u8 = 21;
+
+/// 开启 `parallel` 特性时，超过该长度的文本使用多线程统计字符权重
+#[cfg(feature = "parallel")]
+const PARALLEL_THRESHOLD: usize = 1 << 20;
+
+/// 可参与哈夫曼编码的符号，默认为 `char`，也可以是字节、单词等任意可哈希、可排序的类型
+///
+/// 需要 `Ord` 是为了在权重相同时按符号排序，保证相同输入总是得到相同的树
+pub trait Symbol: Eq + Hash + Ord + Clone {}
+
+impl<T: Eq + Hash + Ord + Clone> Symbol for T {}
+
+/// 共享的哈夫曼树结点
+pub type RefHuffmanTree<S = char> = Rc<RefCell<HuffmanTree<S>>>;
+/// 字符权重，即字符出现的次数
+pub type Weight = u64;
+
+/// 哈夫曼编解码过程中的错误
+#[derive(Debug)]
+pub enum HuffmanError {
+    /// 文件读写失败
+    #[cfg(feature = "std")]
+    Io(std::io::Error),
+    /// 配置内容格式错误，附带出错的内容
+    MalformedConfig(String),
+    /// 文件头魔数不匹配，不是哈夫曼压缩文件
+    InvalidMagic,
+    /// 不支持的文件格式版本
+    UnsupportedVersion(u8),
+    /// 数据在预期位置之前结束
+    UnexpectedEof,
+    /// 解压结果与压缩时记录的校验值不符，文件已损坏
+    ChecksumMismatch,
+    /// 编码表中某个编码是另一个编码的前缀，无法唯一解码
+    AmbiguousCodes,
+    /// 补位数量不在 `0..8` 之内，或补位不全是 0
+    InvalidPadding,
+    /// 限制的最大编码长度不足以区分所有字符
+    MaxLengthTooSmall(u8),
+    /// 字符权重之和超出 [`Weight`] 的范围
+    WeightOverflow,
+    /// 编码表中没有该字符的编码
+    MissingCode(char),
+    /// 压缩文件引用的共享编码表（附带编号）未提供或与提供的不符
+    TableMismatch(u32),
+    /// 不是目录压缩文件
+    NotDirectoryArchive,
+    /// 目录压缩文件中的路径不合法，如绝对路径或包含 `..`
+    InvalidPath(String),
+    /// 配置中有编码表却没有 `space` 补位记录
+    MissingPadding,
+    /// 哈夫曼树中有环或被多处引用的结点，无法得到前缀编码
+    MalformedTree,
+    /// 按文本压缩或解压的内容不是有效的 UTF-8 文本
+    InvalidUtf8,
+    /// 不同字符的数量超过限制的上限
+    AlphabetTooLarge(usize),
+    /// 给出的压缩数据实际上是配置，压缩数据与配置（或输出路径）的位置颠倒了
+    SwappedInputs,
+}
+
+impl Display for HuffmanError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            #[cfg(feature = "std")]
+            Self::Io(e) => write!(f, "文件读写失败：{}", e),
+            Self::MalformedConfig(s) => write!(f, "配置格式错误：{}", s),
+            Self::InvalidMagic => write!(f, "不是有效的哈夫曼压缩文件"),
+            Self::UnsupportedVersion(v) => write!(f, "不支持的压缩文件版本：{}", v),
+            Self::UnexpectedEof => write!(f, "压缩文件不完整"),
+            Self::ChecksumMismatch => write!(f, "校验失败，压缩文件已损坏"),
+            Self::AmbiguousCodes => write!(f, "编码表有歧义，存在互为前缀的编码"),
+            Self::InvalidPadding => write!(f, "压缩数据末尾的补位无效"),
+            Self::MaxLengthTooSmall(len) => write!(f, "最大编码长度 {} 位不足以区分所有字符", len),
+            Self::WeightOverflow => write!(f, "字符权重之和超出范围"),
+            Self::MissingCode(ch) => write!(f, "编码表中没有字符 U+{:04X} 的编码", *ch as u32),
+            Self::TableMismatch(id) => write!(f, "需要编号为 {:08X} 的共享编码表", id),
+            Self::NotDirectoryArchive => write!(f, "不是目录压缩文件"),
+            Self::InvalidPath(path) => write!(f, "压缩文件中的路径不合法：{}", path),
+            Self::MissingPadding => write!(f, "配置中缺少补位记录 space"),
+            Self::MalformedTree => write!(f, "哈夫曼树结构无效，存在环或共用的结点"),
+            Self::InvalidUtf8 => write!(f, "内容不是有效的 UTF-8 文本"),
+            Self::AlphabetTooLarge(max) => write!(f, "不同字符的数量超过上限 {}", max),
+            Self::SwappedInputs => write!(f, "压缩数据与配置的位置颠倒了，请交换两个参数"),
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for HuffmanError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Self::Io(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl From<std::io::Error> for HuffmanError {
+    fn from(e: std::io::Error) -> Self {
+        Self::Io(e)
+    }
+}
 
 /// 哈夫曼树
-pub struct HuffmanTree {
-    pub value: Option<char>,
+pub struct HuffmanTree<S: Symbol = char> {
+    pub value: Option<S>,
     pub weight: Weight,
-    pub parent: Option<RefHuffmanTree>,
-    pub left: Option<RefHuffmanTree>,
-    pub right: Option<RefHuffmanTree>,
+    pub parent: Option<RefHuffmanTree<S>>,
+    pub left: Option<RefHuffmanTree<S>>,
+    pub right: Option<RefHuffmanTree<S>>,
 }
 
-impl HuffmanTree {
+impl<S: Symbol> Default for HuffmanTree<S> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<S: Symbol> HuffmanTree<S> {
     pub fn new() -> Self {
         Self {
             value: None,
@@ -23,233 +151,1264 @@ impl HuffmanTree {
         }
     }
 
-    pub fn build(char_weight: CharWeightMap) -> RefHuffmanTree
+    /// 由字符权重构建哈夫曼树
+    ///
+    /// 根结点的权重为所有权重之和，因此权重之和最大为 `Weight::MAX`，
+    /// 超出时返回 `WeightOverflow`（只可能来自人为构造的权重，例如被篡改的配置）
+    pub fn build(char_weight: CharWeightMap<S>) -> Result<RefHuffmanTree<S>, HuffmanError>
     {
+        Self::from_leaves(char_weight.inner.into_iter().collect())
+    }
+
+    /// 由 `(符号, 权重)` 列表构建哈夫曼树，与 [`build`](Self::build) 相同，但不必先建立权重表
+    ///
+    /// 结果与列表顺序无关；同一符号出现多次时权重相加
+    pub fn from_weights(weights: &[(S, Weight)]) -> Result<RefHuffmanTree<S>, HuffmanError> {
+        let mut leaves = weights.to_vec();
+        leaves.sort_by(|(a, _), (b, _)| a.cmp(b));
+        let mut merged: Vec<(S, Weight)> = Vec::with_capacity(leaves.len());
+        for (symbol, weight) in leaves {
+            match merged.last_mut() {
+                Some((last, total)) if *last == symbol => {
+                    *total = total.checked_add(weight).ok_or(HuffmanError::WeightOverflow)?;
+                },
+                _ => merged.push((symbol, weight)),
+            }
+        }
+        Self::from_leaves(merged)
+    }
+
+    /// 由互不相同的符号及其权重构建哈夫曼树
+    fn from_leaves(mut leaves: Vec<(S, Weight)>) -> Result<RefHuffmanTree<S>, HuffmanError> {
         // 原始结点数量
-        let n = char_weight.len();
+        let n = leaves.len();
+        // 空输入没有任何字符，直接返回一棵空树
+        if n == 0 {
+            return Ok(Rc::new(RefCell::new(Self::new())));
+        }
         // 构建完整哈夫曼树总共需要的结点数量
         let total = 2 * n - 1;
         // 初始化所有结点
         let vec = (0..total)
             .map(|_| Rc::new(RefCell::new(Self::new())))
-            .collect::<Vec<Rc<RefCell<HuffmanTree>>>>();
+            .collect::<Vec<RefHuffmanTree<S>>>();
+
+        // 按 (权重, 下标) 排序的小顶堆，权重相同时优先取下标小的结点
+        let mut heap = BinaryHeap::with_capacity(n);
+
+        // 按 (权重, 字符) 排序后再分配结点，保证相同输入总是得到相同的树
+        leaves.sort_by(|(a, wa), (b, wb)| (wa, a).cmp(&(wb, b)));
 
         // 字符结点赋值
-        char_weight.iter()
+        leaves.into_iter()
             .enumerate()
-            .into_iter()
             .for_each(|(index, (ch, weight))| {
                 // println!("{}: {} ({})", index, &weight, ch);
-                vec[index].borrow_mut().value = Some(*ch);
-                vec[index].borrow_mut().weight = *weight;
+                vec[index].borrow_mut().value = Some(ch);
+                vec[index].borrow_mut().weight = weight;
+                heap.push(Reverse((weight, index)));
             });
 
         for index in n..total {
-            // 找到 [0, index-1] 中权重最小的结点
-            let m1 = Self::find_min(&vec[..index]).unwrap();
-            // 标记父结点为 index 上的结点，下次就不会找到这个
+            // 取出堆中权重最小的两个结点
+            let Reverse((w1, i1)) = heap.pop().unwrap();
+            let Reverse((w2, i2)) = heap.pop().unwrap();
+            let (m1, m2) = (&vec[i1], &vec[i2]);
+            // 标记父结点为 index 上的结点
             m1.borrow_mut().parent = Some(vec[index].clone());
-            // 找到 [0, index-1] 中权重第二小的结点
-            let m2 = Self::find_min(&vec[..index]).unwrap();
-            // 标记该结点的父结点为 index 上的结点。
             m2.borrow_mut().parent = Some(vec[index].clone());
 
-            let w1 = m1.as_ref().borrow().weight;
-            let w2 = m2.as_ref().borrow().weight;
-            let weight = w1 + w2;
+            let weight = w1.checked_add(w2).ok_or(HuffmanError::WeightOverflow)?;
 
             vec[index].borrow_mut().weight = weight;
             vec[index].borrow_mut().left = Some(m1.clone());
             vec[index].borrow_mut().right = Some(m2.clone());
+            heap.push(Reverse((weight, index)));
         }
         // 最后一个结点即为构建好的完整哈夫曼树
-        vec.last().unwrap().clone()
+        Ok(vec.last().unwrap().clone())
+    }
+
+    /// 结点的权重：叶子结点为符号出现的次数，内部结点为其下所有叶子结点的权重之和
+    pub fn weight(&self) -> Weight {
+        self.weight
     }
 
-    /// 获取最小的值
-    fn find_min(tree_slice: &[Rc<RefCell<HuffmanTree>>]) -> Option<Rc<RefCell<HuffmanTree>>> {
-        let mut min = Weight::MAX;
-        let mut result = None;
-        for tree in tree_slice {
-            let tree_cell = tree.as_ref();
-            if tree_cell.borrow().parent.is_none() && tree_cell.borrow().weight < min {
-                min = tree_cell.borrow().weight;
-                result = Some(tree.clone());
+    /// 整棵树的总权重，即根结点的权重，对任意结点调用时沿 `parent` 找到根结点
+    ///
+    /// 由文本统计的权重建成的树，总权重恰好等于编码的符号数量，空树为 0
+    pub fn total_weight(&self) -> Weight {
+        match &self.parent {
+            Some(parent) => parent.as_ref().borrow().total_weight(),
+            None => self.weight,
+        }
+    }
+
+    /// 树高，即根结点到最深叶子结点的边数
+    ///
+    /// 只有根结点（空树或只有一种字符）时为 0；只有一种字符时编码仍为 1 位，
+    /// 其余情况下树高等于最长编码的长度
+    pub fn height(&self) -> usize {
+        let child_height = |child: &Option<RefHuffmanTree<S>>| {
+            child.as_ref().map(|c| c.as_ref().borrow().height() + 1)
+        };
+        child_height(&self.left).max(child_height(&self.right)).unwrap_or(0)
+    }
+
+    /// 所有叶子结点的字符、编码与权重，按深度优先（先左后右）的顺序排列
+    ///
+    /// 只有一种字符时根结点就是叶子结点，与 [`HuffmanBinaryMap::build`] 一致，编码为 1 位 `0`
+    pub fn leaves(&self) -> Vec<LeafInfo<S>> {
+        if let Some(symbol) = &self.value {
+            return vec![LeafInfo { symbol: symbol.clone(), code: vec![false], weight: self.weight }];
+        }
+        let children = [(&self.right, true), (&self.left, false)];
+        let stack = children.iter()
+            .filter_map(|(child, bit)| (*child).clone().map(|child| (vec![*bit], child)))
+            .collect();
+        DfsIter { stack, visited: Set::new() }
+            .filter_map(|(code, node)| {
+                let node = node.as_ref().borrow();
+                node.value.clone().map(|symbol| LeafInfo { symbol, code, weight: node.weight })
+            })
+            .collect()
+    }
+
+    /// 按深度优先（先左后右）的前序遍历树中所有结点，同时给出从根结点到该结点的路径
+    pub fn dfs(tree: &RefHuffmanTree<S>) -> DfsIter<S> {
+        DfsIter { stack: vec![(vec![], tree.clone())], visited: Set::new() }
+    }
+}
+
+impl HuffmanTree {
+    /// 导出为 Graphviz DOT 格式，可用 `dot -Tpng` 等命令画出树
+    ///
+    /// 叶子结点标注字符和权重，内部结点标注权重，边标注 `0`（左）或 `1`（右）。
+    /// 结点以从根结点出发的路径命名，如 `n01`；空白与控制字符显示为 `U+XXXX`
+    pub fn to_dot(&self) -> String {
+        let mut dot = String::from("digraph huffman {\n    node [fontname=\"monospace\"];\n");
+        let label = |value: Option<char>, weight: Weight| match value {
+            Some(ch) if ch.is_whitespace() || ch.is_control() => format!("U+{:04X}\\n{}", ch as u32, weight),
+            Some(ch @ ('"' | '\\')) => format!("\\{}\\n{}", ch, weight),
+            Some(ch) => format!("{}\\n{}", ch, weight),
+            None => weight.to_string(),
+        };
+        let shape = |value: &Option<char>| if value.is_some() { "box" } else { "circle" };
+        dot += &format!("    n [label=\"{}\", shape={}];\n", label(self.value, self.weight), shape(&self.value));
+        let children = [(&self.right, true), (&self.left, false)];
+        let stack = children.iter()
+            .filter_map(|(child, bit)| (*child).clone().map(|child| (vec![*bit], child)))
+            .collect();
+        for (code, node) in (DfsIter { stack, visited: Set::new() }) {
+            let node = node.as_ref().borrow();
+            let name = code.iter().map(|b| if *b { '1' } else { '0' }).collect::<String>();
+            dot += &format!("    n{} [label=\"{}\", shape={}];\n", name, label(node.value, node.weight), shape(&node.value));
+            dot += &format!("    n{} -> n{} [label=\"{}\"];\n", &name[..name.len() - 1], name, &name[name.len() - 1..]);
+        }
+        dot += "}\n";
+        dot
+    }
+}
+
+/// 叶子结点的信息，由 [`HuffmanTree::leaves`] 得到
+#[derive(Debug, Clone, PartialEq)]
+pub struct LeafInfo<S = char> {
+    pub symbol: S,
+    /// 从根结点到该叶子结点的路径，`false` 为左、`true` 为右，即字符的编码
+    pub code: Vec<bool>,
+    pub weight: Weight,
+}
+
+/// 树中结点的深度优先迭代器，由 [`HuffmanTree::dfs`] 创建，产生 `(路径, 结点)`
+///
+/// 已经过的结点不会再次产生，因此手动连接出环的树也能遍历结束
+pub struct DfsIter<S: Symbol = char> {
+    stack: Vec<(Vec<bool>, RefHuffmanTree<S>)>,
+    visited: Set<*const RefCell<HuffmanTree<S>>>,
+}
+
+impl<S: Symbol> Iterator for DfsIter<S> {
+    type Item = (Vec<bool>, RefHuffmanTree<S>);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            let (code, node) = self.stack.pop()?;
+            if !self.visited.insert(Rc::as_ptr(&node)) {
+                continue;
             }
+            {
+                let tree = node.as_ref().borrow();
+                // 先压入右子结点，使左子结点先出栈
+                for (child, bit) in [(&tree.right, true), (&tree.left, false)] {
+                    if let Some(child) = child {
+                        let mut path = code.clone();
+                        path.push(bit);
+                        self.stack.push((path, child.clone()));
+                    }
+                }
+            }
+            return Some((code, node));
         }
-        result
     }
 }
 
 /// 字符权重
-pub struct CharWeightMap {
-    pub inner: HashMap<char, Weight>
+#[derive(Clone)]
+pub struct CharWeightMap<S: Symbol = char> {
+    pub inner: Map<S, Weight>
 }
 
 impl CharWeightMap {
-    pub fn build(input: &String) -> Self {
-        let mut map = HashMap::new();
+    pub fn build(input: &str) -> Self {
+        let mut map = Self { inner: Map::new() };
+        map.update(input);
+        map
+    }
+    /// 继续统计一段文本中的字符
+    ///
+    /// 开启 `parallel` 特性时，较大的文本会切分成多段由多个线程分别统计后合并
+    pub fn update(&mut self, input: &str) {
+        #[cfg(feature = "parallel")]
+        if input.len() >= PARALLEL_THRESHOLD {
+            return self.update_parallel(input);
+        }
+        self.update_serial(input);
+    }
+    fn update_serial(&mut self, input: &str) {
         for (_, c) in input.char_indices() {
-            map.entry(c).or_insert(0).add_assign(1);
+            self.inner.entry(c).or_insert(0).add_assign(1);
+        }
+    }
+    /// 多线程统计：按字符边界把文本切成若干段，每段统计到各自的表中再合并
+    #[cfg(feature = "parallel")]
+    fn update_parallel(&mut self, input: &str) {
+        let threads = std::thread::available_parallelism().map_or(1, |n| n.get());
+        let chunk_len = input.len().div_ceil(threads);
+        let mut chunks = vec![];
+        let mut rest = input;
+        while !rest.is_empty() {
+            let mut end = chunk_len.min(rest.len());
+            while !rest.is_char_boundary(end) {
+                end += 1;
+            }
+            let (chunk, tail) = rest.split_at(end);
+            chunks.push(chunk);
+            rest = tail;
+        }
+        let maps = std::thread::scope(|scope| {
+            let handles = chunks.into_iter()
+                .map(|chunk| scope.spawn(move || {
+                    let mut map = CharWeightMap { inner: Map::new() };
+                    map.update_serial(chunk);
+                    map
+                }))
+                .collect::<Vec<_>>();
+            handles.into_iter().map(|h| h.join().unwrap()).collect::<Vec<_>>()
+        });
+        for map in maps {
+            self.merge(&map).expect("字符数量不会超出权重的范围");
         }
+    }
+}
+
+impl<S: Symbol> CharWeightMap<S> {
+    /// 直接使用已算好的权重，例如从数据库中读出的统计结果，不必拼出一段文本再统计
+    ///
+    /// 关闭 `std` 特性时参数为 `BTreeMap`
+    pub fn from_map(map: Map<S, Weight>) -> Self {
         Self { inner: map }
     }
+    /// 统计任意符号序列中各符号出现的次数
+    pub fn from_symbols<'a>(symbols: impl IntoIterator<Item = &'a S>) -> Self where S: 'a {
+        let mut inner = Map::new();
+        for symbol in symbols {
+            inner.entry(symbol.clone()).or_insert(0).add_assign(1);
+        }
+        Self { inner }
+    }
+    /// 把另一张表的权重逐个符号加到这张表上，用于累计多个输入的统计结果
+    ///
+    /// 同一符号的权重之和超出 `Weight` 的范围时返回 `WeightOverflow`，此时这张表可能只合并了一部分
+    pub fn merge(&mut self, other: &CharWeightMap<S>) -> Result<(), HuffmanError> {
+        for (symbol, weight) in &other.inner {
+            add_weight(&mut self.inner, symbol.clone(), *weight)?;
+        }
+        Ok(())
+    }
     pub fn len(&self) -> usize {
         self.inner.len()
     }
-    pub fn iter(&self) -> Iter<char, Weight> {
+    pub fn is_empty(&self) -> bool {
+        self.inner.is_empty()
+    }
+    /// 不同字符的数量超过 `max_symbols` 时返回 `AlphabetTooLarge`
+    ///
+    /// 建树需要 `2n - 1` 个结点，在建树之前检查，避免为恶意构造的输入分配过多内存
+    pub fn check_alphabet(&self, max_symbols: usize) -> Result<(), HuffmanError> {
+        if self.len() > max_symbols {
+            return Err(HuffmanError::AlphabetTooLarge(max_symbols));
+        }
+        Ok(())
+    }
+    pub fn iter(&self) -> Iter<'_, S, Weight> {
         self.inner.iter()  
     }
+    /// 字符总数，即所有权重之和，超出 `Weight` 的范围时返回 `WeightOverflow`
+    pub fn total(&self) -> Result<Weight, HuffmanError> {
+        self.inner.values().try_fold(0 as Weight, |total, w| total.checked_add(*w)).ok_or(HuffmanError::WeightOverflow)
+    }
+    /// 香农熵 `-Σ p·log2(p)`，即每个字符理论上最少需要的位数
+    ///
+    /// `core` 中没有 `log2`，需要 `std` 特性
+    #[cfg(feature = "std")]
+    pub fn entropy(&self) -> f64 {
+        // 只是统计值，按浮点数求和，权重之和超出范围也能算出
+        let total = self.inner.values().map(|w| *w as f64).sum::<f64>();
+        if total == 0.0 {
+            return 0.0;
+        }
+        self.inner.values()
+            .map(|w| {
+                let p = *w as f64 / total;
+                p * (1.0 / p).log2()
+            })
+            .sum()
+    }
 }
 
-/// 字符二进制映射，表示字符对应的二进制位，可用 bitvec 替代
-pub struct HuffmanBinaryMap {
-    pub inner: HashMap<char, Vec<bool>>
+/// 同一符号的权重相加，超出范围时返回 `WeightOverflow`
+fn add_weight<S: Symbol>(inner: &mut Map<S, Weight>, symbol: S, weight: Weight) -> Result<(), HuffmanError> {
+    let total = inner.entry(symbol).or_insert(0);
+    *total = total.checked_add(weight).ok_or(HuffmanError::WeightOverflow)?;
+    Ok(())
+}
+
+/// 合并多张表，等同于依次 [`merge`](CharWeightMap::merge)，权重之和超出范围时返回 `WeightOverflow`
+impl<S: Symbol> core::iter::FromIterator<CharWeightMap<S>> for Result<CharWeightMap<S>, HuffmanError> {
+    fn from_iter<I: IntoIterator<Item = CharWeightMap<S>>>(iter: I) -> Self {
+        let mut result = CharWeightMap { inner: Map::new() };
+        for map in iter {
+            result.merge(&map)?;
+        }
+        Ok(result)
+    }
 }
 
-impl HuffmanBinaryMap {
-    pub fn build(huffman_tree: RefHuffmanTree) -> Self {
-        let mut map = HashMap::new();
-        Self::tree_dfs(&Some(huffman_tree), &mut map, &mut vec![]);
+/// 由 `(符号, 权重)` 构建，同一符号出现多次时权重相加
+///
+/// 权重之和超出范围时 panic；权重来自不可信的输入时改为逐个 `from_map` 后 [`merge`](CharWeightMap::merge)
+impl<S: Symbol> core::iter::FromIterator<(S, Weight)> for CharWeightMap<S> {
+    fn from_iter<I: IntoIterator<Item = (S, Weight)>>(iter: I) -> Self {
+        let mut inner = Map::new();
+        for (symbol, weight) in iter {
+            add_weight(&mut inner, symbol, weight).expect("字符权重之和超出范围");
+        }
+        Self { inner }
+    }
+}
+
+/// 用于写入配置文件，每行格式为 `U+XXXX:weight`
+impl Display for CharWeightMap {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut weights = self.inner.iter().collect::<Vec<_>>();
+        weights.sort_by_key(|(c, _)| **c);
+        for (c, w) in weights {
+            writeln!(f, "U+{:04X}:{}", *c as u32, w)?;
+        }
+        Ok(())
+    }
+}
+
+/// 字符二进制映射，表示字符对应的二进制位
+///
+/// 编码只按字符存一份，体积与字母表大小相关而与文件大小无关，
+/// 因此仍用 `Vec<bool>` 保存；输出时由 [`BitWriter`](crate::BitWriter) 按高位在前紧凑打包成字节
+///
+/// 编码长度没有上限：权重极不均匀（如斐波那契数列）时编码可能超过 32 甚至 64 位，
+/// 读写都逐位进行，不依赖任何定长的整数缓冲区
+pub struct HuffmanBinaryMap<S: Symbol = char> {
+    pub inner: Map<S, Vec<bool>>
+}
+
+impl<S: Symbol> HuffmanBinaryMap<S> {
+    /// 遍历哈夫曼树得到每个字符的编码
+    ///
+    /// 只借用树，之后仍可用它导出 DOT 或统计树高；
+    /// 树的结点字段是公开的，手动连接出环或共用结点时返回 `MalformedTree`，而不是无限递归
+    pub fn build(huffman_tree: &RefHuffmanTree<S>) -> Result<Self, HuffmanError> {
+        let mut map = Map::new();
+        // 只有一种字符时根结点就是叶子结点，为其分配 1 位编码 `0`
+        if let Some(ch) = huffman_tree.as_ref().borrow().value.clone() {
+            map.insert(ch, vec![false]);
+            return Ok(Self { inner: map });
+        }
+        Self::tree_dfs(huffman_tree, &mut map)?;
+        Ok(Self { inner: map })
+    }
+    /// 字符对应的编码
+    pub fn get(&self, ch: &S) -> Option<&[bool]> {
+        self.inner.get(ch).map(|code| code.as_slice())
+    }
+    pub fn contains(&self, ch: &S) -> bool {
+        self.inner.contains_key(ch)
+    }
+    pub fn len(&self) -> usize {
+        self.inner.len()
+    }
+    pub fn is_empty(&self) -> bool {
+        self.inner.is_empty()
+    }
+    /// 遍历 `(字符, 编码)`，顺序不固定
+    pub fn iter(&self) -> impl Iterator<Item = (&S, &[bool])> + '_ {
+        self.inner.iter().map(|(ch, code)| (ch, code.as_slice()))
+    }
+    /// 按字符权重加权的平均编码长度，即实际每个字符使用的位数
+    pub fn average_code_length(&self, weights: &CharWeightMap<S>) -> f64 {
+        let total = weights.iter().map(|(_, w)| *w as f64).sum::<f64>();
+        if total == 0.0 {
+            return 0.0;
+        }
+        let bits = weights.iter()
+            .map(|(ch, w)| *w as f64 * self.inner.get(ch).map_or(0, |code| code.len()) as f64)
+            .sum::<f64>();
+        bits / total
+    }
+    /// 编码长度的最小值、最大值和（不按权重加权的）平均值
+    pub fn length_stats(&self) -> CodeLengthStats {
+        let lengths = self.inner.values().map(|code| code.len());
+        let count = self.inner.len();
+        CodeLengthStats {
+            min: lengths.clone().min().unwrap_or(0),
+            max: lengths.clone().max().unwrap_or(0),
+            average: if count == 0 { 0.0 } else { lengths.sum::<usize>() as f64 / count as f64 },
+        }
+    }
+    /// 每个字符的编码长度
+    ///
+    /// 权重为 `u64` 时树高不会超过 93（斐波那契数列增长限制），因此长度总能用 `u8` 表示
+    pub fn code_lengths(&self) -> Map<S, u8> {
+        self.inner.iter()
+            .map(|(ch, code)| {
+                let len = u8::try_from(code.len()).expect("编码长度超过 255 位，树不是由 u64 权重构建的");
+                (ch.clone(), len)
+            })
+            .collect()
+    }
+    /// 把超过 `max_len` 的编码长度调整到限制之内
+    ///
+    /// 先统计各长度的编码数量，每次从最长的一层取出两个编码，让其中一个上移一层、
+    /// 另一个与更短一层的某个编码共同下移一层，直到没有超长的编码；
+    /// 再按原有长度顺序把调整后的长度重新分给各字符。`max_len` 不足以容纳所有字符时返回错误
+    pub fn limited_lengths(&self, max_len: u8) -> Result<Map<S, u8>, HuffmanError> {
+        let n = self.inner.len();
+        if n > 1usize.checked_shl(max_len as u32).unwrap_or(usize::MAX) || (n > 0 && max_len == 0) {
+            return Err(HuffmanError::MaxLengthTooSmall(max_len));
+        }
+        let mut symbols = self.code_lengths().into_iter().collect::<Vec<_>>();
+        symbols.sort_by(|(a, la), (b, lb)| (la, a).cmp(&(lb, b)));
+        let longest = symbols.last().map_or(0, |(_, len)| *len as usize);
+        if longest <= max_len as usize {
+            return Ok(symbols.into_iter().collect());
+        }
+        // bits[i] 为长度为 i 的编码数量
+        let mut bits = vec![0usize; longest + 1];
+        symbols.iter().for_each(|(_, len)| bits[*len as usize] += 1);
+        for i in (max_len as usize + 1..=longest).rev() {
+            while bits[i] > 0 {
+                let mut j = i - 2;
+                while bits[j] == 0 {
+                    j -= 1;
+                }
+                bits[i] -= 2;
+                bits[i - 1] += 1;
+                bits[j + 1] += 2;
+                bits[j] -= 1;
+            }
+        }
+        let mut lengths = bits.iter()
+            .enumerate()
+            .flat_map(|(len, count)| core::iter::repeat_n(len as u8, *count));
+        Ok(symbols.into_iter()
+            .map(|(ch, _)| (ch, lengths.next().unwrap()))
+            .collect())
+    }
+    /// 根据编码长度生成范式哈夫曼编码
+    ///
+    /// 按 (长度, 字符) 排序后依次分配，每个编码为上一个编码加一，长度增加时在末尾补 0，
+    /// 因此只需保存各字符的编码长度即可还原整张编码表
+    pub fn from_lengths(lengths: &Map<S, u8>) -> Self {
+        let mut symbols = lengths.iter().collect::<Vec<_>>();
+        symbols.sort_by_key(|(ch, len)| (**len, *ch));
+        let mut map = Map::new();
+        let mut code: Vec<bool> = vec![];
+        for (ch, len) in symbols {
+            code.resize(*len as usize, false);
+            map.insert(ch.clone(), code.clone());
+            Self::increment(&mut code);
+        }
         Self { inner: map }
     }
-    fn tree_dfs(
-        tree: &Option<RefHuffmanTree>, 
-        map: &mut HashMap<char, Vec<bool>>,
-        vec: &mut Vec<bool>
-    ) {
-        if let Some(tree) = tree {
-            let tree = tree.as_ref().borrow();
-            if let Some(ch) = tree.value {
-                map.insert(ch, vec.clone());
+    /// 把二进制位序列当作整数加一：末尾连续的 1 变为 0，其前一位变为 1，溢出时在前面补 1
+    fn increment(code: &mut Vec<bool>) {
+        for b in code.iter_mut().rev() {
+            if *b {
+                *b = false;
+            } else {
+                *b = true;
+                return;
             }
-            vec.push(false);
-            Self::tree_dfs(&tree.left, map, vec);
-            let last = vec.last_mut().unwrap();
-            *last = true;
-            Self::tree_dfs(&tree.right, map, vec);
-            vec.pop();
         }
+        code.insert(0, true);
+    }
+    /// 用显式的栈深度优先遍历，树再深也不会栈溢出
+    ///
+    /// 记录已经过的结点，再次经过同一结点说明树中有环或共用的结点
+    fn tree_dfs(root: &RefHuffmanTree<S>, map: &mut Map<S, Vec<bool>>) -> Result<(), HuffmanError> {
+        let mut visited: Set<*const RefCell<HuffmanTree<S>>> = Set::new();
+        // 当前结点的编码，栈中记录每个结点的父结点编码长度与最后一位
+        let mut code = vec![];
+        let mut stack = vec![(root.clone(), 0, None)];
+        while let Some((node, len, bit)) = stack.pop() {
+            if !visited.insert(Rc::as_ptr(&node)) {
+                return Err(HuffmanError::MalformedTree);
+            }
+            code.truncate(len);
+            code.extend(bit);
+            let tree = node.as_ref().borrow();
+            if let Some(ch) = &tree.value {
+                map.insert(ch.clone(), code.clone());
+            }
+            // 先压入右子结点，使左子结点先出栈
+            for (child, bit) in [(&tree.right, true), (&tree.left, false)] {
+                if let Some(child) = child {
+                    stack.push((child.clone(), code.len(), Some(bit)));
+                }
+            }
+        }
+        Ok(())
     }
 }
 
+/// 编码长度统计，由 [`HuffmanBinaryMap::length_stats`] 得到，没有字符时各项均为 0
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct CodeLengthStats {
+    pub min: usize,
+    pub max: usize,
+    pub average: f64,
+}
+
 /// 用于写入配置文件
+///
+/// 每行格式为 `U+XXXX:bits`，字符以十六进制码点写入，
+/// 因此 `:`、换行符等字符也不会破坏配置文件格式
 impl Display for HuffmanBinaryMap {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         let mut buf = String::new();
-        self.inner.iter()
+        // 按字符排序写出，保证配置文本稳定
+        let mut codes = self.inner.iter().collect::<Vec<_>>();
+        codes.sort_by_key(|(c, _)| **c);
+        codes.into_iter()
             .for_each(|(c, vec)| {
                 let mut bit_str = String::new();
                 vec.iter().for_each(|b| {
                     bit_str += if *b { "1" } else { "0" }
                 });
-                buf += format!("{}:{}\n", *c as u32, bit_str).as_str();
+                buf += format!("U+{:04X}:{}\n", *c as u32, bit_str).as_str();
             });
         f.write_str(buf.as_str())
     }
 }
 
-pub struct HuffmanCodec;
+/// 哈夫曼编解码器
+///
+/// 默认以 `char` 为符号，即 `HuffmanCodec<char>`，提供文本、容器、流等全部接口；
+/// 其他符号类型（如单词、字节）可使用 [`encode_symbols`](Self::encode_symbols) 与
+/// [`decode_symbols`](Self::decode_symbols)，权重表由调用方自行保存。
+///
+/// 绝大多数接口是关联函数，不需要实例；反复压缩大量短文本时可由 [`new`](Self::new) 创建实例并调用
+/// [`encode_into`](HuffmanCodec::encode_into)，复用实例中的权重表与编码表
+///
+/// 实例中的表都是上一次编码留下的，每次编码前清空，保留已分配的容量
+pub struct HuffmanCodec<S: Symbol = char> {
+    weights: CharWeightMap<S>,
+    /// 按 (权重, 字符) 排好序的叶子结点，下标即结点编号
+    leaves: Vec<(S, Weight)>,
+    heap: BinaryHeap<Reverse<(Weight, usize)>>,
+    /// 依次合并出的内部结点的左右子结点编号，内部结点编号从叶子数量开始
+    children: Vec<(usize, usize)>,
+    /// 遍历树时的栈：结点编号、父结点编码长度与最后一位
+    stack: Vec<(usize, usize, Option<bool>)>,
+    /// 遍历到的当前结点的编码
+    path: Vec<bool>,
+    /// 每个字符的编码在 `bits` 中的起始位置与长度
+    codes: Map<S, (usize, usize)>,
+    bits: Vec<bool>,
+}
+
+impl<S: Symbol> Default for HuffmanCodec<S> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<S: Symbol> HuffmanCodec<S> {
+    pub fn new() -> Self {
+        Self {
+            weights: CharWeightMap { inner: Map::new() },
+            leaves: vec![],
+            heap: BinaryHeap::new(),
+            children: vec![],
+            stack: vec![],
+            path: vec![],
+            codes: Map::new(),
+            bits: vec![],
+        }
+    }
+
+    /// 由 `weights` 生成编码表，合并顺序与 [`HuffmanTree::build`] 完全相同，得到的编码也相同，
+    /// 但只用下标记录树的结构，不分配任何结点
+    fn plan_codes(&mut self) -> Result<(), HuffmanError> {
+        self.leaves.clear();
+        self.leaves.extend(self.weights.iter().map(|(symbol, weight)| (symbol.clone(), *weight)));
+        self.leaves.sort_by(|(a, wa), (b, wb)| (wa, a).cmp(&(wb, b)));
+        self.codes.clear();
+        self.bits.clear();
+        let n = self.leaves.len();
+        // 只有一种字符时根结点就是叶子结点，分配 1 位编码 `0`
+        if n <= 1 {
+            if let Some((symbol, _)) = self.leaves.first() {
+                self.codes.insert(symbol.clone(), (0, 1));
+                self.bits.push(false);
+            }
+            return Ok(());
+        }
+        self.heap.clear();
+        self.heap.extend(self.leaves.iter().enumerate().map(|(index, (_, weight))| Reverse((*weight, index))));
+        self.children.clear();
+        for index in n..2 * n - 1 {
+            let Reverse((w1, i1)) = self.heap.pop().unwrap();
+            let Reverse((w2, i2)) = self.heap.pop().unwrap();
+            self.children.push((i1, i2));
+            self.heap.push(Reverse((w1.checked_add(w2).ok_or(HuffmanError::WeightOverflow)?, index)));
+        }
+        self.stack.clear();
+        self.stack.push((2 * n - 2, 0, None));
+        self.path.clear();
+        while let Some((node, len, bit)) = self.stack.pop() {
+            self.path.truncate(len);
+            self.path.extend(bit);
+            if node < n {
+                self.codes.insert(self.leaves[node].0.clone(), (self.bits.len(), self.path.len()));
+                self.bits.extend_from_slice(&self.path);
+                continue;
+            }
+            // 先压入右子结点，使左子结点先出栈
+            let (left, right) = self.children[node - n];
+            self.stack.push((right, self.path.len(), Some(true)));
+            self.stack.push((left, self.path.len(), Some(false)));
+        }
+        Ok(())
+    }
+
+    /// 编码任意符号序列，返回压缩后的字节数组和用于解码的符号权重
+    pub fn encode_symbols(symbols: &[S]) -> Result<(Vec<u8>, CharWeightMap<S>), HuffmanError> {
+        let weight_map = CharWeightMap::from_symbols(symbols);
+        let bit_map = HuffmanBinaryMap::build(&HuffmanTree::build(weight_map.clone())?)?;
+        let mut result = vec![];
+        let mut writer = BitWriter::new(&mut result);
+        for symbol in symbols {
+            writer.write_bits(bit_map.get(symbol).unwrap())?;
+        }
+        writer.finish()?;
+        Ok((result, weight_map))
+    }
+
+    /// 解码由 [`encode_symbols`](Self::encode_symbols) 生成的数据，`weights` 为编码时得到的符号权重
+    ///
+    /// 符号数量即权重之和；数据不足时返回 `UnexpectedEof`，末尾多出非 0 的位或字节时返回 `InvalidPadding`
+    pub fn decode_symbols(source: &[u8], weights: &CharWeightMap<S>) -> Result<Vec<S>, HuffmanError> {
+        let count = weights.total()? as usize;
+        // 数量来自调用方给出的权重，不按其预先分配内存
+        let mut result = vec![];
+        if count == 0 {
+            return if source.is_empty() { Ok(result) } else { Err(HuffmanError::InvalidPadding) };
+        }
+        let root = HuffmanTree::build(weights.clone())?;
+        // 补位数量没有记录，读完所有符号后剩余的位即为补位
+        let mut bits = BitReader::new(source, 0);
+        while result.len() < count {
+            result.push(Self::read_symbol(&mut bits, &root)?);
+        }
+        bits.finish()?;
+        Ok(result)
+    }
+
+    /// 从根结点沿 `bits` 走到叶子结点，读出一个符号
+    fn read_symbol(bits: &mut BitReader<&[u8]>, root: &RefHuffmanTree<S>) -> Result<S, HuffmanError> {
+        let mut node = root.clone();
+        loop {
+            if let Some(symbol) = &node.as_ref().borrow().value {
+                // 只有一种符号时根结点就是叶子结点，编码为 1 位 `0`
+                if Rc::ptr_eq(&node, root) {
+                    bits.read_bit()?.ok_or(HuffmanError::UnexpectedEof)?;
+                }
+                return Ok(symbol.clone());
+            }
+            let bit = bits.read_bit()?.ok_or(HuffmanError::UnexpectedEof)?;
+            let child = if bit { node.as_ref().borrow().right.clone() } else { node.as_ref().borrow().left.clone() };
+            node = child.ok_or(HuffmanError::UnexpectedEof)?;
+        }
+    }
+}
 
 impl HuffmanCodec {
     /// 哈夫曼编码
-    pub fn encode(source: &String) -> (Vec<u8>, String) {
-        // 构建字符权重映射
-        let weight_map = CharWeightMap::build(&source);
+    ///
+    /// 返回压缩后的字节数组和配置文本，配置文本可由 [`DecodeConfig::build`] 解析
+    pub fn encode(source: &str) -> Result<(Vec<u8>, String), HuffmanError> {
+        let mut result = vec![];
+        let config = Self::new().encode_into(source, &mut result)?;
+        Ok((result, config))
+    }
+
+    /// 与 [`encode`](Self::encode) 相同，但压缩数据追加到 `out` 末尾，只返回配置文本
+    ///
+    /// 统计权重与生成编码表时复用实例中上一次的表，不建立哈夫曼树的结点，按位直接写入 `out`
+    /// 而不经过 [`BitWriter`] 的缓冲区；逐条压缩大量短文本时配合清空后重复使用的 `out`，
+    /// 除了返回的配置文本之外几乎不再分配内存
+    pub fn encode_into(&mut self, source: &str, out: &mut Vec<u8>) -> Result<String, HuffmanError> {
+        self.weights.inner.clear();
+        self.weights.update(source);
+        self.plan_codes()?;
+        let (mut byte, mut len) = (0u8, 0u8);
+        for ch in source.chars() {
+            let (start, code_len) = self.codes[&ch];
+            for bit in &self.bits[start..start + code_len] {
+                if *bit {
+                    byte |= BitOrder::MsbFirst.mask(len);
+                }
+                len += 1;
+                if len == 8 {
+                    out.push(byte);
+                    byte = 0;
+                    len = 0;
+                }
+            }
+        }
+        if len != 0 {
+            out.push(byte);
+        }
+        let space = padding_bits(len as usize);
+        Ok(Self::config_text(space, source.len(), source.chars().count(), "weight", &self.weights))
+    }
+
+    /// 与 [`encode`](Self::encode) 相同，但不同字符超过 `max_symbols` 个时在建树之前返回 `AlphabetTooLarge`
+    pub fn encode_with_max_symbols(source: &str, max_symbols: usize) -> Result<(Vec<u8>, String), HuffmanError> {
+        let weights = CharWeightMap::build(source);
+        weights.check_alphabet(max_symbols)?;
+        Self::encode_with_weights(source, &weights)
+    }
+
+    /// 使用给定的字符权重编码，不再重新统计 `source`，例如使用在整个语料上累计的权重
+    ///
+    /// 配置中记录的是 `weights` 而非 `source` 的权重；`source` 中有 `weights` 没有的字符时返回 `MissingCode`
+    pub fn encode_with_weights(source: &str, weights: &CharWeightMap) -> Result<(Vec<u8>, String), HuffmanError> {
+        Self::encode_with_order(source, weights, BitOrder::MsbFirst)
+    }
+
+    /// 与 [`encode_with_weights`](Self::encode_with_weights) 相同，压缩数据按 `order` 的位序打包
+    pub(crate) fn encode_with_order(
+        source: &str,
+        weights: &CharWeightMap,
+        order: BitOrder,
+    ) -> Result<(Vec<u8>, String), HuffmanError> {
         // 构建哈夫曼树
-        let tree = HuffmanTree::build(weight_map);
+        let tree = HuffmanTree::build(weights.clone())?;
         // 哈夫曼二进制映射表
-        let bit_map = HuffmanBinaryMap::build(tree);
-        // println!("{}", bit_map);
+        let bit_map = HuffmanBinaryMap::build(&tree)?;
+        let (result, space) = Self::pack_with_order(source, &bit_map, order)?;
+        // 返回的结果
+        Ok((
+            result, // 压缩后的字节数组
+            Self::config_text(space, source.len(), source.chars().count(), "weight", weights), // 配置文件内容
+        ))
+    }
+
+    /// 范式哈夫曼编码
+    ///
+    /// 与 [`encode`](Self::encode) 相同，但编码按范式规则分配，配置中只记录每个字符的编码长度
+    pub fn encode_canonical(source: &str) -> Result<(Vec<u8>, String), HuffmanError> {
+        let weight_map = CharWeightMap::build(source);
+        let lengths = HuffmanBinaryMap::build(&HuffmanTree::build(weight_map)?)?.code_lengths();
+        Self::encode_with_lengths(source, lengths)
+    }
+
+    /// 限制编码长度的范式哈夫曼编码，所有编码都不超过 `max_len` 位
+    ///
+    /// 以少量压缩率换取固定的最大编码宽度；`max_len` 位不足以区分所有字符时返回 `MaxLengthTooSmall`
+    pub fn encode_limited(source: &str, max_len: u8) -> Result<(Vec<u8>, String), HuffmanError> {
+        let weight_map = CharWeightMap::build(source);
+        let lengths = HuffmanBinaryMap::build(&HuffmanTree::build(weight_map)?)?.limited_lengths(max_len)?;
+        Self::encode_with_lengths(source, lengths)
+    }
+
+    /// 按给定的编码长度生成范式编码并编码，配置中只记录编码长度
+    fn encode_with_lengths(source: &str, lengths: Map<char, u8>) -> Result<(Vec<u8>, String), HuffmanError> {
+        let bit_map = HuffmanBinaryMap::from_lengths(&lengths);
+        let (result, space) = Self::pack(source, &bit_map)?;
+        let mut table = lengths.into_iter().collect::<Vec<_>>();
+        table.sort();
+        let table = table.into_iter()
+            .map(|(ch, len)| format!("U+{:04X}:{}\n", ch as u32, len))
+            .collect::<String>();
+        let config = Self::config_text(space, source.len(), source.chars().count(), "length", &table);
+        Ok((result, config))
+    }
+
+    /// 以结束符代替补位记录的编码：额外加入一个权重为 1 的结束符写在原文之后，解码读到结束符即停止
+    ///
+    /// 返回压缩数据与原文的字符权重（不含结束符），由调用方保存，解码时不需要 `space` 与 `count`
+    pub fn encode_with_eos(source: &str) -> Result<(Vec<u8>, CharWeightMap), HuffmanError> {
+        let weights = CharWeightMap::build(source);
+        let bit_map = HuffmanBinaryMap::build(&Self::eos_tree(&weights)?)?;
+        let mut result = vec![];
+        let mut writer = BitWriter::new(&mut result);
+        for symbol in source.chars().map(Some).chain(core::iter::once(None)) {
+            writer.write_bits(bit_map.get(&symbol).unwrap())?;
+        }
+        writer.finish()?;
+        Ok((result, weights))
+    }
+
+    /// 解码由 [`encode_with_eos`](Self::encode_with_eos) 生成的数据，`weights` 为编码时返回的字符权重
+    ///
+    /// 数据在结束符之前结束时返回 `UnexpectedEof`，结束符之后还有非 0 的位或字节时返回 `InvalidPadding`
+    pub fn decode_with_eos(source: &[u8], weights: &CharWeightMap) -> Result<String, HuffmanError> {
+        let root = Self::eos_tree(weights)?;
+        let mut result = String::new();
+        let mut bits = BitReader::new(source, 0);
+        while let Some(ch) = HuffmanCodec::read_symbol(&mut bits, &root)? {
+            result.push(ch);
+        }
+        bits.finish()?;
+        Ok(result)
+    }
+
+    /// 在字符之外加入结束符 `None` 构建哈夫曼树
+    fn eos_tree(weights: &CharWeightMap) -> Result<RefHuffmanTree<Option<char>>, HuffmanError> {
+        let leaves = weights.iter()
+            .map(|(ch, weight)| (Some(*ch), *weight))
+            .chain(core::iter::once((None, 1)))
+            .collect::<Vec<_>>();
+        HuffmanTree::from_weights(&leaves)
+    }
+
+    /// 按编码表把文本打包成字节，返回打包结果和末尾补位数量
+    ///
+    /// 写出之前先检查每个字符都有编码，编码表中缺少某个字符或其编码为空时返回 `MissingCode`，不会写到一半才出错
+    pub fn pack(source: &str, bit_map: &HuffmanBinaryMap) -> Result<(Vec<u8>, u8), HuffmanError> {
+        Self::pack_with_order(source, bit_map, BitOrder::MsbFirst)
+    }
+
+    pub(crate) fn pack_with_order(source: &str, bit_map: &HuffmanBinaryMap, order: BitOrder) -> Result<(Vec<u8>, u8), HuffmanError> {
+        // 空编码不占任何位，解码时无法还原
+        if let Some(ch) = source.chars().find(|ch| bit_map.get(ch).is_none_or(|code| code.is_empty())) {
+            return Err(HuffmanError::MissingCode(ch));
+        }
         let mut result: Vec<u8> = vec![];
-        let (mut buf, mut count) = (0, 0);
+        let mut writer = BitWriter::with_order(&mut result, order);
         for (_, ch) in source.char_indices() {
-            let vec = bit_map.inner.get(&ch).unwrap();
-            vec.iter().for_each(|b| {
-                buf <<= 1;
-                if *b { buf |= 1 }
-                count += 1;
-                if count >= 8 {
-                    result.push(buf);
-                    buf = 0;
-                    count = 0;
-                }
-            })
+            let vec = bit_map.get(&ch).unwrap();
+            writer.write_bits(vec).expect("写入内存不会失败");
         }
         // 末尾补位数量
-        let mut space = 0u8;
-        if count != 0 {
-            space = 8 - count;
-            buf <<= space;
-            result.push(buf);
+        let space = writer.finish().expect("写入内存不会失败");
+        Ok((result, space))
+    }
+
+    /// 哈夫曼解码，`decode_map` 为 [`encode`](Self::encode) 生成的配置
+    pub fn decode(source: &[u8], decode_map: &DecodeConfig) -> Result<String, HuffmanError> {
+        // 防止内存频繁分配，直接定义容量；配置中的容量不可信，每一位最多解出一个字符
+        let mut result = String::with_capacity(decode_map.capacity.min(source.len().saturating_mul(8)));
+        for ch in Self::decode_iter(source, decode_map) {
+            result.push(ch?);
         }
-        // 返回的结果
-        (
-            result, // 压缩后的字节数组
-            format!("space:{}\ncapacity:{}\n{}", space, source.len(), bit_map), // 配置文件内容
-        )
+        Ok(result)
     }
 
-    pub fn decode(source: &[u8], decode_map: &DecodeConfig) -> String {
-        // 防止内存频繁分配，直接定义容量
-        let mut result = String::with_capacity(decode_map.capacity);
-        let bit_str = source.iter()
-            .map(|num| {
-                format!("{u8:>0width$b}", u8=num, width=8)
-            })
-            .collect::<Vec<String>>()
-            .join("");
-        // println!("二进制序列：{}", bit_str);
+    /// 逐个解出字符，不必一次生成整个字符串，可配合 `take`、`filter` 等按需解码
+    pub fn decode_iter<'a>(source: &'a [u8], decode_map: &'a DecodeConfig) -> DecodeIter<'a> {
+        DecodeIter::new(source, decode_map)
+    }
+
+    /// 使用预先构建的前缀树解码 [`pack`](Self::pack) 打包的数据，`space` 为打包时返回的补位数量
+    ///
+    /// 多段数据共用同一编码表时只需构建一次前缀树，不必为每段数据重新解析配置
+    pub fn decode_with_tree(source: &[u8], tree: &DecodeTree, space: u8) -> Result<String, HuffmanError> {
+        DecodeIter::with_tree(source, tree, None, space, BitOrder::MsbFirst).collect()
+    }
+
+    /// 生成配置文本，`table` 为编码表的记录方式，`entries` 为编码表内容，
+    /// 默认只记录字符权重，解码时据此重建同一棵哈夫曼树
+    pub(crate) fn config_text(
+        space: u8,
+        capacity: usize,
+        count: usize,
+        table: &str,
+        entries: &dyn Display,
+    ) -> String {
+        format!("space:{}\ncapacity:{}\ncount:{}\ntable:{}\n{}", space, capacity, count, table, entries)
+    }
+}
+
+/// 沿前缀树逐位解码
+pub(crate) struct BitDecoder<'a> {
+    tree: &'a DecodeTree,
+    /// 原文字符数量，未记录时解码到数据结尾
+    count: Option<usize>,
+    node: RefHuffmanTree,
+    symbols: usize,
+    position: usize,
+    /// 读到转义符后正在读取的码点：已读入的位与位数
+    raw: Option<(u32, u8)>,
+}
 
-        let mut tmp_str = String::with_capacity(20);
-        let last_idx = bit_str.len() - decode_map.space as usize;
-        for (i, ch) in bit_str.char_indices() {
-            if i >= last_idx {
-                break;
+impl<'a> BitDecoder<'a> {
+    #[cfg(feature = "std")]
+    pub fn new(config: &'a DecodeConfig) -> Self {
+        Self::with_tree(&config.tree, config.count)
+    }
+
+    pub fn with_tree(tree: &'a DecodeTree, count: Option<usize>) -> Self {
+        Self { tree, count, node: tree.root.clone(), symbols: 0, position: 0, raw: None }
+    }
+
+    /// 已解码出的字符数量
+    #[cfg(feature = "std")]
+    pub fn symbols(&self) -> usize {
+        self.symbols
+    }
+
+    /// 已读取的位数，不含最后一个字节的补位
+    #[cfg(feature = "std")]
+    pub fn position(&self) -> usize {
+        self.position
+    }
+
+    /// 已解码出配置中记录的字符数量
+    pub fn is_done(&self) -> bool {
+        self.count.is_some_and(|c| self.symbols >= c)
+    }
+
+    /// 沿树向左(0)或向右(1)走一步，到达叶子结点时返回解出的字符
+    ///
+    /// 到达转义符时不返回字符，之后的 [`ESCAPE_BITS`] 位直接是字符的码点
+    pub fn step(&mut self, bit: bool) -> Result<Option<char>, HuffmanError> {
+        if let Some((value, len)) = self.raw {
+            let (value, len) = (value << 1 | u32::from(bit), len + 1);
+            self.position += 1;
+            if len < ESCAPE_BITS {
+                self.raw = Some((value, len));
+                return Ok(None);
+            }
+            self.raw = None;
+            let ch = char::from_u32(value).ok_or_else(|| {
+                HuffmanError::MalformedConfig(format!("第 {} 位转义的码点 U+{:04X} 无效", self.position, value))
+            })?;
+            self.symbols += 1;
+            return Ok(Some(ch));
+        }
+        let next = {
+            let tree = self.node.as_ref().borrow();
+            if bit { tree.right.clone() } else { tree.left.clone() }
+        };
+        self.node = next.ok_or_else(|| {
+            HuffmanError::MalformedConfig(format!("第 {} 位没有对应的编码", self.position))
+        })?;
+        self.position += 1;
+        // 到达叶子结点即得到一个字符，回到根结点继续
+        let value = self.node.as_ref().borrow().value;
+        if value.is_some() {
+            self.node = self.tree.root.clone();
+            if value == self.tree.escape {
+                self.raw = Some((0, 0));
+                return Ok(None);
+            }
+            self.symbols += 1;
+        }
+        Ok(value)
+    }
+
+    /// 检查解码出的字符数量是否与配置一致
+    pub fn finish(&self) -> Result<(), HuffmanError> {
+        if self.raw.is_some() || self.count.is_some_and(|c| self.symbols < c) {
+            return Err(HuffmanError::UnexpectedEof);
+        }
+        Ok(())
+    }
+}
+
+/// 逐个解出字符的迭代器，由 [`HuffmanCodec::decode_iter`] 创建
+///
+/// 数据有误时产生一次错误后结束
+pub struct DecodeIter<'a> {
+    decoder: BitDecoder<'a>,
+    bits: BitReader<&'a [u8]>,
+    /// 出错或已经结束
+    done: bool,
+    /// 没有任何数据
+    empty: bool,
+}
+
+impl<'a> DecodeIter<'a> {
+    fn new(source: &'a [u8], config: &'a DecodeConfig) -> Self {
+        Self::with_tree(source, &config.tree, config.count, config.space, config.order)
+    }
+
+    fn with_tree(source: &'a [u8], tree: &'a DecodeTree, count: Option<usize>, space: u8, order: BitOrder) -> Self {
+        let decoder = BitDecoder::with_tree(tree, count);
+        Self { decoder, bits: BitReader::with_order(source, space, order), done: false, empty: source.is_empty() }
+    }
+
+    fn fail(&mut self, e: HuffmanError) -> Option<Result<char, HuffmanError>> {
+        self.done = true;
+        Some(Err(e))
+    }
+}
+
+impl<'a> Iterator for DecodeIter<'a> {
+    type Item = Result<char, HuffmanError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.done {
+            return None;
+        }
+        while !self.decoder.is_done() {
+            let bit = match self.bits.read_bit() {
+                Ok(Some(bit)) => bit,
+                Ok(None) => break,
+                // 记录了字符数量却没有任何数据时，数据被截断比补位有误更能说明问题
+                Err(e) if self.empty => return self.fail(self.decoder.finish().err().unwrap_or(e)),
+                Err(e) => return self.fail(e),
+            };
+            match self.decoder.step(bit) {
+                Ok(Some(ch)) => return Some(Ok(ch)),
+                Ok(None) => (),
+                Err(e) => return self.fail(e),
+            }
+        }
+        self.done = true;
+        self.decoder.finish().err().map(Err)
+    }
+}
+
+/// 由编码表还原并校验过的前缀树，`0` 为左子树，`1` 为右子树
+///
+/// 可在共用同一编码表的多段数据之间复用，见 [`HuffmanCodec::decode_with_tree`]；克隆只复制引用
+#[derive(Clone)]
+pub struct DecodeTree {
+    root: RefHuffmanTree,
+    /// 转义符，解码到该字符时改为直接读取码点，见 [`HuffmanCodec::encode_sampled`](crate::HuffmanCodec::encode_sampled)
+    escape: Option<char>,
+}
+
+impl DecodeTree {
+    /// 由编码表构建前缀树，某个编码是另一个编码的前缀时返回 `AmbiguousCodes`
+    pub fn from_bit_map(bit_map: &HuffmanBinaryMap) -> Result<Self, HuffmanError> {
+        let map = bit_map.iter()
+            .map(|(ch, code)| (code.iter().map(|b| if *b { '1' } else { '0' }).collect(), *ch))
+            .collect();
+        Self::build(&map)
+    }
+    /// 根结点，只有叶子结点带有字符
+    pub fn root(&self) -> &RefHuffmanTree {
+        &self.root
+    }
+    /// 由 `编码文本 -> 字符` 的编码表还原前缀树
+    ///
+    /// 若某个编码是另一个编码的前缀，则编码表有歧义，返回 `AmbiguousCodes`
+    fn build(map: &Map<String, char>) -> Result<Self, HuffmanError> {
+        let root = Rc::new(RefCell::new(HuffmanTree::new()));
+        for (bits, ch) in map {
+            let mut node = root.clone();
+            for b in bits.chars() {
+                let next = {
+                    let mut tree = node.borrow_mut();
+                    // 途经的结点已经是某个字符，说明该字符的编码是当前编码的前缀
+                    if tree.value.is_some() {
+                        return Err(HuffmanError::AmbiguousCodes);
+                    }
+                    let child = if b == '1' { &mut tree.right } else { &mut tree.left };
+                    child.get_or_insert_with(|| Rc::new(RefCell::new(HuffmanTree::new()))).clone()
+                };
+                node = next;
             }
-            tmp_str.push(ch);
-            if let Some(mch) = decode_map.get(&tmp_str) {
-                result.push(*mch);
-                tmp_str.clear();
+            let mut tree = node.borrow_mut();
+            // 终点已有子结点，说明当前编码是其他编码的前缀
+            if tree.left.is_some() || tree.right.is_some() {
+                return Err(HuffmanError::AmbiguousCodes);
             }
+            tree.value = Some(*ch);
         }
-        result
+        Ok(Self { root, escape: None })
     }
 }
 
 /// 配置文件的配置
 pub struct DecodeConfig {
-    pub inner: HashMap<String, char>,
+    pub inner: Map<String, char>,
     pub space: u8,
     pub capacity: usize,
+    /// 原文字符数量，旧版配置文件中没有该项
+    pub count: Option<usize>,
+    /// 由编码表还原的前缀树，解码时沿树逐位查找
+    pub tree: DecodeTree,
+    /// 压缩数据的位序，记录在压缩文件头而非配置中，解析配置时总为高位在前
+    pub order: BitOrder,
 }
 impl DecodeConfig {
-    pub fn build(source: &String) -> Self {
-        let mut map = HashMap::default();
-        let (mut space, mut capacity) = (0u8, 0usize);
-        let arr = source.split("\n");
+    /// 解析配置文本
+    pub fn build(source: &str) -> Result<Self, HuffmanError> {
+        Self::build_with_table(source, None)
+    }
+    /// 解析配置文本，配置引用共享编码表或字典时使用 `shared`，未提供或编号不符时返回 `TableMismatch`
+    pub(crate) fn build_with_table(source: &str, shared: Option<&CodeTable>) -> Result<Self, HuffmanError> {
+        let mut map = Map::default();
+        let (mut space, mut capacity, mut count) = (None, 0usize, None);
+        // 是否记录了编码表，原样保存的压缩文件只有 `capacity` 一项
+        let mut has_table = false;
+        // 引用的共享编码表编号
+        let mut table_id = None;
+        // 抽样编码时样本中没有的字符写为转义符加码点
+        let mut escape = None;
+        // 配置记录的是编码、权重、编码长度还是字典之外的权重，旧版配置中没有 `table` 项，记录的是编码
+        let mut table = "code";
+        let mut weights = CharWeightMap::build("");
+        let mut lengths = Map::new();
+        // 去掉行尾的 `\r`，在 Windows 上另存过的 CRLF 配置也能解析
+        let arr = source.split('\n').map(|line| line.strip_suffix('\r').unwrap_or(line));
         for s in arr {
-            let pair: Vec<&str> = s.split(":").collect();
-            if pair.len() != 2 {
-                continue;
-            }
-            let (ch, bit) = (pair[0], pair[1]);
+            let (ch, bit) = match s.split_once(':') {
+                Some(pair) => pair,
+                None => continue,
+            };
+            let malformed = || HuffmanError::MalformedConfig(s.to_owned());
             match ch {
                 "space" => {
-                    space = u8::from_str_radix(bit, 10).unwrap();
+                    let bits: u8 = bit.parse().map_err(|_| malformed())?;
+                    // 补位只会出现在最后一个字节中，不可能达到 8 位
+                    if bits >= 8 {
+                        return Err(HuffmanError::InvalidPadding);
+                    }
+                    space = Some(bits);
                     continue;
                 },
                 "capacity" => {
-                    capacity = usize::from_str_radix(bit, 10).unwrap();
+                    capacity = bit.parse().map_err(|_| malformed())?;
+                    continue;
+                },
+                "count" => {
+                    count = Some(bit.parse().map_err(|_| malformed())?);
+                    continue;
+                },
+                "table" => {
+                    if !matches!(bit, "code" | "weight" | "length" | "shared" | "dictionary") {
+                        return Err(malformed());
+                    }
+                    table = bit;
+                    has_table = true;
+                    continue;
+                },
+                "id" => {
+                    table_id = Some(u32::from_str_radix(bit, 16).map_err(|_| malformed())?);
+                    continue;
+                },
+                "escape" => {
+                    escape = Some(Self::parse_symbol(bit).ok_or_else(malformed)?);
                     continue;
                 },
                 _ => (),
             }
-            map.insert(bit.to_owned(), char::from_u32(u32::from_str_radix(ch, 10).unwrap()).unwrap());
+            let symbol = Self::parse_symbol(ch).ok_or_else(malformed)?;
+            has_table = true;
+            match table {
+                "weight" | "dictionary" => {
+                    weights.inner.insert(symbol, bit.parse().map_err(|_| malformed())?);
+                    continue;
+                },
+                "length" => {
+                    match bit.parse() {
+                        Ok(len) if len > 0 => lengths.insert(symbol, len),
+                        _ => return Err(malformed()),
+                    };
+                    continue;
+                },
+                _ => (),
+            }
+            if bit.is_empty() || !bit.chars().all(|b| b == '0' || b == '1') {
+                return Err(malformed());
+            }
+            // 两个字符使用同一编码
+            if map.insert(bit.to_owned(), symbol).is_some() {
+                return Err(HuffmanError::AmbiguousCodes);
+            }
         };
-        Self { inner: map, space, capacity }
+        // 缺少补位记录时无法判断最后一个字节中哪些位是补位，不能默认为 0
+        let space = match space {
+            Some(space) => space,
+            None if !has_table => 0,
+            None => return Err(HuffmanError::MissingPadding),
+        };
+        if table == "shared" || table == "dictionary" {
+            let id = table_id.ok_or_else(|| HuffmanError::MalformedConfig("缺少共享编码表编号".to_owned()))?;
+            let shared = match shared {
+                Some(shared) if shared.id() == id => shared,
+                _ => return Err(HuffmanError::TableMismatch(id)),
+            };
+            if table == "shared" {
+                let codes = shared.decode_map();
+                let (inner, tree) = (codes.inner.clone(), codes.tree.clone());
+                return Ok(Self { inner, space, capacity, count, tree, order: BitOrder::MsbFirst });
+            }
+            // 字典的权重加上配置中补充的权重
+            weights.merge(shared.weights())?;
+        }
+        let bit_map = match table {
+            // 由权重重建哈夫曼树，得到与编码时完全相同的编码表
+            "weight" | "dictionary" => Some(HuffmanBinaryMap::build(&HuffmanTree::build(weights)?)?),
+            // 由编码长度还原范式哈夫曼编码
+            "length" => Some(HuffmanBinaryMap::from_lengths(&lengths)),
+            _ => None,
+        };
+        if let Some(bit_map) = bit_map {
+            map = bit_map.inner.into_iter()
+                .map(|(ch, code)| (code.iter().map(|b| if *b { '1' } else { '0' }).collect(), ch))
+                .collect();
+        }
+        let mut tree = DecodeTree::build(&map)?;
+        if let Some(ch) = escape {
+            // 转义符必须是编码表中的一个字符
+            if !map.values().any(|value| *value == ch) {
+                return Err(HuffmanError::MalformedConfig(format!("escape:U+{:04X}", ch as u32)));
+            }
+            tree.escape = escape;
+        }
+        Ok(Self { inner: map, space, capacity, count, tree, order: BitOrder::MsbFirst })
+    }
+    /// 解析配置中的字符：`U+XXXX` 为十六进制码点，纯数字为旧版的十进制码点
+    ///
+    /// 整个字段必须恰好是一个码点，多余的字符（包括 `+` 号）或代理区码点都视为格式错误
+    pub(crate) fn parse_symbol(s: &str) -> Option<char> {
+        let (digits, radix) = match s.strip_prefix("U+") {
+            Some(hex) => (hex, 16),
+            None => (s, 10),
+        };
+        if digits.is_empty() || !digits.chars().all(|c| c.is_digit(radix)) {
+            return None;
+        }
+        char::from_u32(u32::from_str_radix(digits, radix).ok()?)
     }
-    pub fn get(&self, k: &String) -> Option<&char> {
+    pub fn get(&self, k: &str) -> Option<&char> {
         self.inner.get(k)
     }
 }
\ No newline at end of file
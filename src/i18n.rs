@@ -0,0 +1,294 @@
+//! 命令行的提示与消息，按 `LANG` 等环境变量选择中文或英文
+//!
+//! 只影响命令行输出，库本身的错误信息仍为中文；英文环境下由 [`Msg::Error`] 逐项翻译。
+
+use std::{
+    env,
+    fmt::{self, Display},
+    sync::OnceLock,
+};
+
+use rust_huffman::HuffmanError;
+
+/// 界面语言
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum Lang {
+    Zh,
+    En,
+}
+
+impl Lang {
+    /// 依次查看 `LC_ALL`、`LC_MESSAGES`、`LANG`，取第一个非空的值，以 `zh` 开头时为中文，其余均为英文
+    fn from_env() -> Self {
+        let locale = ["LC_ALL", "LC_MESSAGES", "LANG"]
+            .iter()
+            .filter_map(|key| env::var(key).ok())
+            .find(|value| !value.is_empty())
+            .unwrap_or_default();
+        if locale.starts_with("zh") { Lang::Zh } else { Lang::En }
+    }
+
+    /// 当前进程的界面语言，只在第一次调用时读取环境变量
+    pub fn current() -> Self {
+        static LANG: OnceLock<Lang> = OnceLock::new();
+        *LANG.get_or_init(Self::from_env)
+    }
+}
+
+const USAGE_ZH: &str = "用法：
+  rust-huffman                                交互模式
+  rust-huffman <文件> [-o <输出文件>]             根据文件自动选择压缩或解压
+  rust-huffman compress <文件> [-o <输出文件>]    压缩文件
+  rust-huffman decompress <文件> [-o <输出文件>]  解压文件
+  rust-huffman verify <原文件> <压缩文件>          校验压缩文件能否还原出原文件
+  rust-huffman stats <文件>                      输出字符频率与编码表
+  rust-huffman build-table <语料> [-o <编码表>]   由语料生成共享编码表（默认 table.json）
+
+选项：
+  --force-compress     不论文件类型，总是压缩
+  --force-decompress   不论文件类型，总是解压
+  --stats              压缩后输出哈夫曼树高与编码长度统计
+  --dump-tree <文件>   压缩时把哈夫曼树导出为 Graphviz DOT 文件
+  --table <文件>       用 build-table 生成的编码表压缩 / 解压 / 校验，不再统计权重
+  --threads <N>        分块压缩 / 并行解压，使用 N 个线程
+  --max-symbols <N>    压缩文本时不同字符超过 N 个则报错，限制内存占用
+  --sample <N>         只用开头 N 字节建立编码表，一遍读完整个文件
+  --no-clobber         输出路径已存在时报错，不覆盖
+  --rm                 成功并校验通过后删除输入文件
+  --keep               保留输入文件（默认）
+  --estimate           只预估压缩后的大小，不写出文件
+  -q, --quiet          不输出状态信息，只输出错误
+  -h, --help           输出本用法说明
+
+文件路径为 - 时读取标准输入 / 写到标准输出；状态信息总是写到标准错误";
+
+const USAGE_EN: &str = "Usage:
+  rust-huffman                                 interactive mode
+  rust-huffman <file> [-o <output>]            compress or decompress depending on the file
+  rust-huffman compress <file> [-o <output>]   compress a file
+  rust-huffman decompress <file> [-o <output>] decompress a file
+  rust-huffman verify <original> <archive>     check that the archive restores the original
+  rust-huffman stats <file>                    print symbol frequencies and the code table
+  rust-huffman build-table <corpus> [-o <table>] build a shared code table (default table.json)
+
+Options:
+  --force-compress     always compress, whatever the file looks like
+  --force-decompress   always decompress, whatever the file looks like
+  --stats              print Huffman tree height and code length statistics after compressing
+  --dump-tree <file>   write the Huffman tree as a Graphviz DOT file when compressing
+  --table <file>       compress / decompress / verify with a table from build-table instead of counting
+  --threads <N>        compress in blocks / decompress in parallel on N threads
+  --max-symbols <N>    fail when the text has more than N distinct symbols, bounding memory use
+  --sample <N>         build the code table from the first N bytes and read the file once
+  --no-clobber         fail instead of overwriting an existing output path
+  --rm                 delete the input after it has been processed and verified
+  --keep               keep the input (default)
+  --estimate           only predict the compressed size, write nothing
+  -q, --quiet          print errors only, no status messages
+  -h, --help           print this usage
+
+Use - as a path to read from stdin / write to stdout; status messages always go to stderr";
+
+/// 命令行输出的每一条消息，显示时按 [`Lang::current`] 选择语言
+pub enum Msg<'a> {
+    Usage,
+    MissingOutputPath,
+    MissingThreadCount,
+    MissingMaxSymbols,
+    MissingSampleSize,
+    MissingDumpTreePath,
+    MissingTablePath,
+    InvalidThreadCount(&'a str),
+    InvalidMaxSymbols(&'a str),
+    InvalidSampleSize(&'a str),
+    UnknownOption(&'a str),
+    ExtraArgument(&'a str),
+    MissingInput,
+    OutputExists(&'a str),
+    VerifyFailed { archive: &'a str, original: &'a str },
+    VerifyPassed { archive: &'a str, original: &'a str },
+    Failed(&'a HuffmanError),
+    InteractiveTitle,
+    InteractiveMenu,
+    AskCompressPath,
+    AskDecompressPath,
+    AskSavePath,
+    CompressFailed(&'a HuffmanError),
+    DecompressFailed(&'a HuffmanError),
+    Stdout,
+    DirCompressed { dir: &'a str, output: &'a str },
+    FileCompressed { output: &'a str },
+    Estimated,
+    OriginalSize(usize),
+    CompressedSize(usize),
+    CompressedSizeWithConfig { total: usize, config: usize },
+    CompressedSizeWithBlocks { total: usize, blocks: usize },
+    Ratio(f64),
+    LargerThanInput(usize),
+    Entropy { minimum: f64, actual: f64 },
+    StatsHeader,
+    TreeHeight(usize),
+    TreeDumped { path: &'a str },
+    TableBuilt { path: &'a str, symbols: usize },
+    CodeLengths { min: usize, max: usize, average: f64 },
+    DirDecompressed { output: &'a str },
+    FileDecompressed { output: &'a str },
+    InputRemoved(&'a str),
+    InputKept(&'a str),
+    Error(&'a HuffmanError),
+}
+
+impl<'a> Display for Msg<'a> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let zh = Lang::current() == Lang::Zh;
+        match *self {
+            Msg::Usage => f.write_str(if zh { USAGE_ZH } else { USAGE_EN }),
+            Msg::MissingOutputPath if zh => write!(f, "-o 之后缺少输出文件路径"),
+            Msg::MissingOutputPath => write!(f, "missing output path after -o"),
+            Msg::MissingThreadCount if zh => write!(f, "--threads 之后缺少线程数"),
+            Msg::MissingThreadCount => write!(f, "missing thread count after --threads"),
+            Msg::MissingDumpTreePath if zh => write!(f, "--dump-tree 之后缺少文件路径"),
+            Msg::MissingDumpTreePath => write!(f, "missing file path after --dump-tree"),
+            Msg::MissingTablePath if zh => write!(f, "--table 之后缺少编码表路径"),
+            Msg::MissingTablePath => write!(f, "missing table path after --table"),
+            Msg::InvalidThreadCount(n) if zh => write!(f, "线程数无效：{}", n),
+            Msg::InvalidThreadCount(n) => write!(f, "invalid thread count: {}", n),
+            Msg::MissingMaxSymbols if zh => write!(f, "--max-symbols 之后缺少字符数"),
+            Msg::MissingMaxSymbols => write!(f, "missing symbol count after --max-symbols"),
+            Msg::InvalidMaxSymbols(n) if zh => write!(f, "字符数上限无效：{}", n),
+            Msg::InvalidMaxSymbols(n) => write!(f, "invalid symbol limit: {}", n),
+            Msg::MissingSampleSize if zh => write!(f, "--sample 之后缺少样本字节数"),
+            Msg::MissingSampleSize => write!(f, "missing sample size after --sample"),
+            Msg::InvalidSampleSize(n) if zh => write!(f, "样本字节数无效：{}", n),
+            Msg::InvalidSampleSize(n) => write!(f, "invalid sample size: {}", n),
+            Msg::UnknownOption(arg) if zh => write!(f, "未知选项：{}", arg),
+            Msg::UnknownOption(arg) => write!(f, "unknown option: {}", arg),
+            Msg::ExtraArgument(arg) if zh => write!(f, "多余的参数：{}", arg),
+            Msg::ExtraArgument(arg) => write!(f, "unexpected argument: {}", arg),
+            Msg::MissingInput if zh => write!(f, "缺少输入文件路径"),
+            Msg::MissingInput => write!(f, "missing input path"),
+            Msg::OutputExists(path) if zh => write!(f, "{} 已存在，指定了 --no-clobber 时不覆盖", path),
+            Msg::OutputExists(path) => write!(f, "{} already exists, not overwriting with --no-clobber", path),
+            Msg::VerifyFailed { archive, original } if zh => {
+                write!(f, "校验失败：{} 解压后与 {} 不同", archive, original)
+            },
+            Msg::VerifyFailed { archive, original } => {
+                write!(f, "verification failed: {} does not decompress to {}", archive, original)
+            },
+            Msg::VerifyPassed { archive, original } if zh => {
+                write!(f, "校验通过：{} 可以完整还原 {}", archive, original)
+            },
+            Msg::VerifyPassed { archive, original } => {
+                write!(f, "verified: {} restores {} exactly", archive, original)
+            },
+            Msg::Failed(e) if zh => write!(f, "\n操作失败：{}", Msg::Error(e)),
+            Msg::Failed(e) => write!(f, "\nfailed: {}", Msg::Error(e)),
+            Msg::InteractiveTitle if zh => write!(f, "【哈夫曼压缩】"),
+            Msg::InteractiveTitle => write!(f, "[Huffman compression]"),
+            Msg::InteractiveMenu if zh => write!(f, "1. 压缩文件\t2. 解压文件\t3. 退出"),
+            Msg::InteractiveMenu => write!(f, "1. Compress\t2. Decompress\t3. Quit"),
+            Msg::AskCompressPath if zh => write!(f, "待压缩文件路径名："),
+            Msg::AskCompressPath => write!(f, "File to compress:"),
+            Msg::AskDecompressPath if zh => write!(f, "待解压文件路径名："),
+            Msg::AskDecompressPath => write!(f, "File to decompress:"),
+            Msg::AskSavePath if zh => write!(f, "请输入保存文件路径："),
+            Msg::AskSavePath => write!(f, "Save as:"),
+            Msg::CompressFailed(e) if zh => write!(f, "\n压缩失败：{}\n", Msg::Error(e)),
+            Msg::CompressFailed(e) => write!(f, "\ncompression failed: {}\n", Msg::Error(e)),
+            Msg::DecompressFailed(e) if zh => write!(f, "\n解压失败：{}\n", Msg::Error(e)),
+            Msg::DecompressFailed(e) => write!(f, "\ndecompression failed: {}\n", Msg::Error(e)),
+            Msg::Stdout if zh => write!(f, "标准输出"),
+            Msg::Stdout => write!(f, "stdout"),
+            Msg::DirCompressed { dir, output } if zh => write!(f, "\n压缩成功！\n目录 {} 保存为: {}", dir, output),
+            Msg::DirCompressed { dir, output } => write!(f, "\nCompressed!\nDirectory {} saved as: {}", dir, output),
+            Msg::FileCompressed { output } if zh => write!(f, "\n压缩成功！\n文件保存为: {}", output),
+            Msg::FileCompressed { output } => write!(f, "\nCompressed!\nSaved as: {}", output),
+            Msg::Estimated if zh => write!(f, "预估结果（未写出文件）："),
+            Msg::Estimated => write!(f, "Estimate (nothing written):"),
+            Msg::OriginalSize(n) if zh => write!(f, "压缩前大小：{} 字节", n),
+            Msg::OriginalSize(n) => write!(f, "Original size: {} bytes", n),
+            Msg::CompressedSize(n) if zh => write!(f, "压缩后大小：{} 字节", n),
+            Msg::CompressedSize(n) => write!(f, "Compressed size: {} bytes", n),
+            Msg::CompressedSizeWithConfig { total, config } if zh => {
+                write!(f, "压缩后大小：{} 字节（其中文件头与配置 {} 字节）", total, config)
+            },
+            Msg::CompressedSizeWithConfig { total, config } => {
+                write!(f, "Compressed size: {} bytes ({} bytes of header and config)", total, config)
+            },
+            Msg::CompressedSizeWithBlocks { total, blocks } if zh => {
+                write!(f, "压缩后大小：{} 字节（{} 块）", total, blocks)
+            },
+            Msg::CompressedSizeWithBlocks { total, blocks } => {
+                write!(f, "Compressed size: {} bytes ({} blocks)", total, blocks)
+            },
+            Msg::Ratio(ratio) if zh => write!(f, "压缩比率： {:.2}%", ratio * 100.0),
+            Msg::Ratio(ratio) => write!(f, "Ratio: {:.2}%", ratio * 100.0),
+            Msg::LargerThanInput(extra) if zh => write!(
+                f,
+                "警告：压缩后总大小反而更大（多出 {} 字节），已改为原样保存；这样的小文件或随机数据不必压缩",
+                extra,
+            ),
+            Msg::LargerThanInput(extra) => write!(
+                f,
+                "warning: the archive is larger than the input ({} extra bytes) and was stored uncompressed; \
+                 small files and random data are not worth compressing",
+                extra,
+            ),
+            Msg::Entropy { minimum, actual } if zh => {
+                write!(f, "理论最小：{:.4} bits/符号，实际：{:.4} bits/符号", minimum, actual)
+            },
+            Msg::Entropy { minimum, actual } => {
+                write!(f, "Theoretical minimum: {:.4} bits/symbol, actual: {:.4} bits/symbol", minimum, actual)
+            },
+            Msg::StatsHeader if zh => write!(f, "字符\t频率\t编码\t长度"),
+            Msg::StatsHeader => write!(f, "Symbol\tFrequency\tCode\tLength"),
+            Msg::TreeHeight(height) if zh => write!(f, "树高：{}", height),
+            Msg::TreeHeight(height) => write!(f, "Tree height: {}", height),
+            Msg::TreeDumped { path } if zh => write!(f, "哈夫曼树已导出至：{}", path),
+            Msg::TreeDumped { path } => write!(f, "Huffman tree written to: {}", path),
+            Msg::TableBuilt { path, symbols } if zh => write!(f, "编码表已保存至：{}（{} 个字符）", path, symbols),
+            Msg::TableBuilt { path, symbols } => write!(f, "Code table written to: {} ({} symbols)", path, symbols),
+            Msg::CodeLengths { min, max, average } if zh => {
+                write!(f, "编码长度：最短 {} 位，最长 {} 位，平均 {:.2} 位", min, max, average)
+            },
+            Msg::CodeLengths { min, max, average } => {
+                write!(f, "Code lengths: min {} bits, max {} bits, average {:.2} bits", min, max, average)
+            },
+            Msg::DirDecompressed { output } if zh => write!(f, "\n解压成功！\n目录已保存至：{}", output),
+            Msg::DirDecompressed { output } => write!(f, "\nDecompressed!\nDirectory saved to: {}", output),
+            Msg::FileDecompressed { output } if zh => write!(f, "\n解压成功！\n文件已保存至：{}", output),
+            Msg::FileDecompressed { output } => write!(f, "\nDecompressed!\nSaved to: {}", output),
+            Msg::InputRemoved(path) if zh => write!(f, "校验通过，已删除 {}", path),
+            Msg::InputRemoved(path) => write!(f, "verified, removed {}", path),
+            Msg::InputKept(path) if zh => write!(f, "目录与标准输入 / 输出无法校验，未删除 {}", path),
+            Msg::InputKept(path) => write!(f, "kept {}: directories and stdin / stdout cannot be verified", path),
+            Msg::Error(e) if zh => write!(f, "{}", e),
+            Msg::Error(e) => write_error_en(f, e),
+        }
+    }
+}
+
+/// 库的错误信息为中文，英文环境下逐项翻译
+fn write_error_en(f: &mut fmt::Formatter<'_>, e: &HuffmanError) -> fmt::Result {
+    match e {
+        HuffmanError::Io(e) => write!(f, "I/O error: {}", e),
+        HuffmanError::MalformedConfig(s) => write!(f, "malformed config: {}", s),
+        HuffmanError::InvalidMagic => write!(f, "not a Huffman archive"),
+        HuffmanError::UnsupportedVersion(v) => write!(f, "unsupported archive version: {}", v),
+        HuffmanError::UnexpectedEof => write!(f, "archive is truncated"),
+        HuffmanError::ChecksumMismatch => write!(f, "checksum mismatch, the archive is corrupted"),
+        HuffmanError::AmbiguousCodes => write!(f, "ambiguous code table: one code is a prefix of another"),
+        HuffmanError::InvalidPadding => write!(f, "invalid padding at the end of the data"),
+        HuffmanError::MaxLengthTooSmall(len) => write!(f, "a maximum code length of {} bits cannot fit every symbol", len),
+        HuffmanError::WeightOverflow => write!(f, "symbol weights overflow"),
+        HuffmanError::MissingCode(ch) => write!(f, "no code for U+{:04X} in the code table", *ch as u32),
+        HuffmanError::TableMismatch(id) => write!(f, "shared code table {:08X} is required", id),
+        HuffmanError::NotDirectoryArchive => write!(f, "not a directory archive"),
+        HuffmanError::InvalidPath(path) => write!(f, "invalid path in archive: {}", path),
+        HuffmanError::MissingPadding => write!(f, "config has no space record"),
+        HuffmanError::MalformedTree => write!(f, "malformed Huffman tree: it has a cycle or a shared node"),
+        HuffmanError::InvalidUtf8 => write!(f, "content is not valid UTF-8 text"),
+        HuffmanError::AlphabetTooLarge(max) => write!(f, "more than {} distinct symbols", max),
+        HuffmanError::SwappedInputs => write!(f, "the compressed data and the config are swapped; swap the two arguments"),
+    }
+}
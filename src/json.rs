@@ -0,0 +1,219 @@
+//! JSON 格式的配置，便于其他程序读写编码表
+//!
+//! 格式为：
+//!
+//! ```text
+//! {"space":3,"capacity":11,"count":11,"codes":{"U+0061":"0","U+0062":"10"}}
+//! ```
+//!
+//! `codes` 中字符以 `U+XXXX` 十六进制码点表示，编码为 `0` / `1` 组成的字符串，
+//! 因此字符串中不会出现需要转义的字符，解析时也不支持转义。
+//!
+//! 共享编码表 [`CodeTable`] 同样可以保存为 JSON，记录每个字符的权重：
+//!
+//! ```text
+//! {"weights":{"U+0061":5,"U+0062":2}}
+//! ```
+
+use std::collections::HashSet;
+
+use crate::huffman::{CharWeightMap, DecodeConfig, HuffmanCodec, HuffmanError};
+use crate::table::CodeTable;
+
+/// 配置格式
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ConfigFormat {
+    /// 默认的逐行文本格式，由 [`DecodeConfig::build`] 解析
+    #[default]
+    Text,
+    /// JSON 格式，由 [`DecodeConfig::from_json`] 解析
+    Json,
+}
+
+impl HuffmanCodec {
+    /// 哈夫曼编码，按指定格式输出配置
+    ///
+    /// 文本格式与 [`encode`](Self::encode) 相同；JSON 格式直接记录每个字符的编码
+    pub fn encode_with_format(source: &str, format: ConfigFormat) -> Result<(Vec<u8>, String), HuffmanError> {
+        let (result, config) = Self::encode(source)?;
+        match format {
+            ConfigFormat::Text => Ok((result, config)),
+            ConfigFormat::Json => Ok((result, DecodeConfig::build(&config)?.to_json())),
+        }
+    }
+}
+
+impl DecodeConfig {
+    /// 输出 JSON 格式的配置，字符按码点排序
+    pub fn to_json(&self) -> String {
+        let mut codes = self.inner.iter().collect::<Vec<_>>();
+        codes.sort_by_key(|(_, ch)| **ch);
+        let codes = codes.into_iter()
+            .map(|(code, ch)| format!("\"U+{:04X}\":\"{}\"", *ch as u32, code))
+            .collect::<Vec<_>>()
+            .join(",");
+        let count = self.count.map(|count| format!("\"count\":{},", count)).unwrap_or_default();
+        format!("{{\"space\":{},\"capacity\":{},{}\"codes\":{{{}}}}}", self.space, self.capacity, count, codes)
+    }
+
+    /// 解析 JSON 格式的配置
+    ///
+    /// 各字段顺序不限，`count` 可以省略，未知字段视为格式错误
+    pub fn from_json(source: &str) -> Result<Self, HuffmanError> {
+        let mut reader = JsonReader { rest: source };
+        let (mut space, mut capacity, mut count) = (None::<u8>, 0usize, None::<usize>);
+        let mut codes = vec![];
+        reader.object(|reader, key| {
+            match key {
+                "space" => space = Some(reader.number()?),
+                "capacity" => capacity = reader.number()?,
+                "count" => count = Some(reader.number()?),
+                "codes" => reader.object(|reader, symbol| {
+                    // 键必须是一个字符，否则 `count`、`table` 等键会被当作文本配置中的其他字段
+                    let ch = DecodeConfig::parse_symbol(symbol)
+                        .ok_or_else(|| HuffmanError::MalformedConfig(symbol.to_owned()))?;
+                    let code = reader.string()?;
+                    codes.push(format!("U+{:04X}:{}", ch as u32, code));
+                    Ok(())
+                })?,
+                _ => return Err(reader.malformed()),
+            }
+            Ok(())
+        })?;
+        reader.finish()?;
+        // 转成文本格式交给 `build`，复用其中对字符、编码、补位与歧义的校验
+        let mut text = format!("capacity:{}\n", capacity);
+        if let Some(space) = space {
+            text += &format!("space:{}\n", space);
+        }
+        if let Some(count) = count {
+            text += &format!("count:{}\n", count);
+        }
+        text += "table:code\n";
+        codes.iter().for_each(|line| {
+            text += line;
+            text += "\n";
+        });
+        Self::build(&text)
+    }
+}
+
+impl CodeTable {
+    /// 输出 JSON 格式的编码表，字符按码点排序，编号与 [`to_text`](Self::to_text) 保存的相同
+    pub fn to_json(&self) -> String {
+        let mut weights = self.weights().iter().collect::<Vec<_>>();
+        weights.sort_by_key(|(ch, _)| **ch);
+        let weights = weights.into_iter()
+            .map(|(ch, weight)| format!("\"U+{:04X}\":{}", *ch as u32, weight))
+            .collect::<Vec<_>>()
+            .join(",");
+        format!("{{\"weights\":{{{}}}}}", weights)
+    }
+
+    /// 读取 [`to_json`](Self::to_json) 保存的编码表，未知字段视为格式错误
+    pub fn from_json(source: &str) -> Result<Self, HuffmanError> {
+        let mut reader = JsonReader { rest: source };
+        let mut weights = CharWeightMap::build("");
+        reader.object(|reader, key| match key {
+            "weights" => reader.object(|reader, symbol| {
+                let ch = DecodeConfig::parse_symbol(symbol)
+                    .ok_or_else(|| HuffmanError::MalformedConfig(symbol.to_owned()))?;
+                weights.inner.insert(ch, reader.number()?);
+                Ok(())
+            }),
+            _ => Err(reader.malformed()),
+        })?;
+        reader.finish()?;
+        Self::from_weights(weights)
+    }
+}
+
+/// 只支持配置所需子集的 JSON 读取器：对象、无转义字符串和非负整数
+struct JsonReader<'a> {
+    rest: &'a str,
+}
+
+impl<'a> JsonReader<'a> {
+    fn malformed(&self) -> HuffmanError {
+        let near = self.rest.chars().take(16).collect::<String>();
+        HuffmanError::MalformedConfig(format!("JSON 格式错误：{}", near))
+    }
+
+    fn skip_whitespace(&mut self) {
+        self.rest = self.rest.trim_start();
+    }
+
+    /// 顶层对象之后只能有空白
+    fn finish(&mut self) -> Result<(), HuffmanError> {
+        self.skip_whitespace();
+        if !self.rest.is_empty() {
+            return Err(self.malformed());
+        }
+        Ok(())
+    }
+
+    /// 跳过空白后读取指定的符号
+    fn expect(&mut self, token: char) -> Result<(), HuffmanError> {
+        self.skip_whitespace();
+        match self.rest.strip_prefix(token) {
+            Some(rest) => {
+                self.rest = rest;
+                Ok(())
+            },
+            None => Err(self.malformed()),
+        }
+    }
+
+    /// 读取对象，每读到一个键就调用 `f` 读取对应的值
+    fn object(
+        &mut self,
+        mut f: impl FnMut(&mut Self, &'a str) -> Result<(), HuffmanError>,
+    ) -> Result<(), HuffmanError> {
+        self.expect('{')?;
+        let mut keys = HashSet::new();
+        self.skip_whitespace();
+        if let Some(rest) = self.rest.strip_prefix('}') {
+            self.rest = rest;
+            return Ok(());
+        }
+        loop {
+            let key = self.string()?;
+            // 重复的键说明配置有误，不能悄悄覆盖前一个值
+            if !keys.insert(key) {
+                return Err(HuffmanError::MalformedConfig(format!("JSON 中重复的键：{}", key)));
+            }
+            self.expect(':')?;
+            f(self, key)?;
+            self.skip_whitespace();
+            match self.rest.chars().next() {
+                Some(',') => self.rest = &self.rest[1..],
+                Some('}') => {
+                    self.rest = &self.rest[1..];
+                    return Ok(());
+                },
+                _ => return Err(self.malformed()),
+            }
+        }
+    }
+
+    fn string(&mut self) -> Result<&'a str, HuffmanError> {
+        self.expect('"')?;
+        // JSON 字符串中本就不允许出现控制字符，也保证转成文本配置时不会多出一行
+        let end = self.rest.find(|c: char| c == '"' || c == '\\' || c.is_control())
+            .ok_or_else(|| self.malformed())?;
+        if !self.rest[end..].starts_with('"') {
+            return Err(self.malformed());
+        }
+        let s = &self.rest[..end];
+        self.rest = &self.rest[end + 1..];
+        Ok(s)
+    }
+
+    fn number<T: std::str::FromStr>(&mut self) -> Result<T, HuffmanError> {
+        self.skip_whitespace();
+        let end = self.rest.find(|c: char| !c.is_ascii_digit()).unwrap_or(self.rest.len());
+        let value = self.rest[..end].parse().map_err(|_| self.malformed())?;
+        self.rest = &self.rest[end..];
+        Ok(value)
+    }
+}
@@ -0,0 +1,86 @@
+//! 哈夫曼编码压缩 / 解压
+//!
+//! 最简单的入口是 [`compress`] 与 [`decompress`]，二者在内存中读写单个自包含的压缩文件内容：
+//!
+//! ```
+//! let archive = rust_huffman::compress(b"hello huffman");
+//! let data = rust_huffman::decompress(&archive).unwrap();
+//! assert_eq!(data, b"hello huffman");
+//! ```
+//!
+//! 需要在解压之前检查压缩文件时，[`HfmArchive::try_from`](HfmArchive) 校验文件头并读出元数据与编码表，
+//! 再由 [`HfmArchive::decode`] 解压。
+//!
+//! 处理文本时可使用 [`HuffmanCodec::encode_to_container`] 与 [`HuffmanCodec::decode_from_container`]，
+//! 以字符为符号编码，解压直接得到 `String`。
+//!
+//! 如需将压缩数据与配置分开保存，可使用 [`HuffmanCodec::encode`] 得到压缩数据和配置文本，
+//! 再用 [`DecodeConfig::build`] 解析配置并交给 [`HuffmanCodec::decode`] 解码；
+//! 需要 JSON 格式的配置时使用 [`HuffmanCodec::encode_with_format`] 与 [`DecodeConfig::from_json`]。
+//!
+//! 压缩任意二进制数据时使用 [`ByteHuffmanCodec`]，它以字节而非字符为符号，
+//! 也可以用 [`ByteHuffmanCodec::encode_dir`] 把整个目录压缩成一个文件。
+//! 大文件可用 [`ByteHuffmanCodec::encode_blocks`] 分块后由多个线程并行压缩。
+//! 连续重复的字节较多时（如图像、日志），[`ByteHuffmanCodec::encode_rle_then_huffman`] 先做游程编码再压缩。
+//!
+//! 压缩大量相似的文件时，可由样本构建一张 [`CodeTable`]（可保存为文本或 JSON），通过 [`HuffmanCodec::with_table`] 共用。
+//! 也可用 [`HuffmanCodec::encode_with_dictionary`] 以一段公共文本作为预设字典，字典之外的字符仍可出现。
+//!
+//! 符号不限于字符，任何实现了 [`Symbol`] 的类型（如单词）都可以通过
+//! [`HuffmanCodec::encode_symbols`] 与 [`HuffmanCodec::decode_symbols`] 编码。
+//!
+//! 无法两遍扫描输入时可使用 [`AdaptiveHuffmanCodec`]，它边编码边更新哈夫曼树，不需要配置。
+//!
+//! 处理大文件时可使用 [`HuffmanCodec::encode_reader`] 与 [`HuffmanCodec::decode_reader`]
+//! 按块读写，避免把整个文件读入内存。多个压缩文件首尾相接保存时，可用 [`HuffmanCodec::decode_all`] 逐个解出。
+//!
+//! 默认开启的 `std` 特性提供文件读写、流式读写与压缩文件格式。关闭后（`default-features = false`）
+//! crate 为 `no_std`，只依赖 `core` 与 `alloc`，保留建树、编码表、[`HuffmanCodec::encode`] /
+//! [`HuffmanCodec::decode`] 等纯算法部分以及按位读写；哈希表换成 `BTreeMap`，
+//! [`BitWriter`] / [`BitReader`] 改为读写内存中的字节数组。
+
+#![cfg_attr(not(feature = "std"), no_std)]
+
+extern crate alloc;
+
+#[cfg(feature = "std")]
+mod adaptive;
+mod bits;
+#[cfg(feature = "std")]
+mod block;
+#[cfg(feature = "std")]
+mod bytes;
+#[cfg(feature = "std")]
+mod container;
+mod crc32;
+#[cfg(feature = "std")]
+mod dir;
+mod huffman;
+#[cfg(feature = "std")]
+mod json;
+#[cfg(feature = "std")]
+mod rle;
+#[cfg(feature = "std")]
+mod stream;
+mod table;
+
+#[cfg(feature = "std")]
+pub use adaptive::AdaptiveHuffmanCodec;
+#[cfg(not(feature = "std"))]
+pub use bits::io;
+pub use bits::{padding_bits, BitOrder, BitReader, BitWriter};
+#[cfg(feature = "std")]
+pub use bytes::{compress, compress_bytes, decompress, decompress_bytes, ByteHuffmanCodec};
+#[cfg(feature = "std")]
+pub use container::{compression_ratio, CompressionReport, ContainerMeta, HfmArchive};
+pub use huffman::{
+    CharWeightMap, CodeLengthStats, DecodeConfig, DecodeIter, DecodeTree, DfsIter, HuffmanBinaryMap, HuffmanCodec,
+    HuffmanError, HuffmanTree, LeafInfo, RefHuffmanTree, Symbol, Weight,
+};
+#[cfg(feature = "std")]
+pub use json::ConfigFormat;
+#[cfg(feature = "std")]
+pub use stream::DecodeOutcome;
+pub use table::CodeTable;
+#[cfg(feature = "std")]
+pub use table::SharedTableCodec;
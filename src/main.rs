@@ -1,29 +1,284 @@
-use std::{fs::File, io::{Read, Write}, process::exit};
+mod i18n;
 
-use crate::huffman::*;
+use std::{
+    env,
+    ffi::OsStr,
+    fs::{self, File},
+    io::{self, BufRead, BufReader, BufWriter, Cursor, IsTerminal, Read, Seek, Write},
+    path::Path,
+    process::exit,
+    time::{Duration, UNIX_EPOCH},
+};
 
-mod huffman;
+use rust_huffman::*;
+
+use i18n::Msg;
+
+/// 子命令
+#[derive(Clone, Copy)]
+enum Command {
+    Compress,
+    Decompress,
+    Verify,
+    Stats,
+    BuildTable,
+    /// 输出用法说明
+    Help,
+}
+
+/// 命令行参数
+#[derive(Default)]
+struct Args {
+    /// 未指定时根据输入文件自动判断
+    command: Option<Command>,
+    input: String,
+    /// `verify` 命令的压缩文件
+    archive: Option<String>,
+    output: Option<String>,
+    /// 压缩后输出树的统计信息
+    stats: bool,
+    /// 分块压缩 / 并行解压使用的线程数
+    threads: Option<usize>,
+    /// 压缩时把哈夫曼树导出为 DOT 文件
+    dump_tree: Option<String>,
+    /// 输出路径已存在时报错，不覆盖
+    no_clobber: bool,
+    /// 压缩 / 解压成功并校验通过后删除输入文件
+    remove: bool,
+    /// 只预估压缩后的大小，不写出文件
+    estimate: bool,
+    /// 压缩文本时允许的最多不同字符数
+    max_symbols: Option<usize>,
+    /// 只用开头这么多字节建立编码表
+    sample: Option<usize>,
+    /// 不输出状态信息，只输出错误
+    quiet: bool,
+    /// 压缩 / 解压时使用的共享编码表
+    table: Option<String>,
+}
+
+impl Args {
+    fn parse(args: &[String]) -> Result<Self, String> {
+        let (mut command, rest) = match args[0].as_str() {
+            "compress" => (Some(Command::Compress), &args[1..]),
+            "decompress" => (Some(Command::Decompress), &args[1..]),
+            "verify" => (Some(Command::Verify), &args[1..]),
+            "stats" => (Some(Command::Stats), &args[1..]),
+            "build-table" => (Some(Command::BuildTable), &args[1..]),
+            _ => (None, args),
+        };
+        let (mut inputs, mut output, mut stats, mut threads, mut dump_tree) = (vec![], None, false, None, None);
+        let (mut no_clobber, mut remove, mut estimate, mut max_symbols, mut sample) = (false, false, false, None, None);
+        let (mut quiet, mut table) = (false, None);
+        let mut iter = rest.iter();
+        while let Some(arg) = iter.next() {
+            match arg.as_str() {
+                "-o" | "--output" => {
+                    output = Some(iter.next().ok_or_else(|| Msg::MissingOutputPath.to_string())?.clone());
+                },
+                "--force-compress" => command = Some(Command::Compress),
+                "--force-decompress" => command = Some(Command::Decompress),
+                "--stats" => stats = true,
+                "-q" | "--quiet" => quiet = true,
+                "-h" | "--help" => return Ok(Self { command: Some(Command::Help), ..Self::default() }),
+                "--no-clobber" => no_clobber = true,
+                "--keep" => remove = false,
+                "--rm" => remove = true,
+                "--estimate" => {
+                    estimate = true;
+                    command = Some(Command::Compress);
+                },
+                "--table" => {
+                    table = Some(iter.next().ok_or_else(|| Msg::MissingTablePath.to_string())?.clone());
+                },
+                "--dump-tree" => {
+                    dump_tree = Some(iter.next().ok_or_else(|| Msg::MissingDumpTreePath.to_string())?.clone());
+                },
+                "--threads" => {
+                    let n = iter.next().ok_or_else(|| Msg::MissingThreadCount.to_string())?;
+                    match n.parse() {
+                        Ok(n) if n > 0 => threads = Some(n),
+                        _ => return Err(Msg::InvalidThreadCount(n).to_string()),
+                    }
+                },
+                "--max-symbols" => {
+                    let n = iter.next().ok_or_else(|| Msg::MissingMaxSymbols.to_string())?;
+                    match n.parse() {
+                        Ok(n) if n > 0 => max_symbols = Some(n),
+                        _ => return Err(Msg::InvalidMaxSymbols(n).to_string()),
+                    }
+                },
+                "--sample" => {
+                    let n = iter.next().ok_or_else(|| Msg::MissingSampleSize.to_string())?;
+                    match n.parse() {
+                        Ok(n) if n > 0 => sample = Some(n),
+                        _ => return Err(Msg::InvalidSampleSize(n).to_string()),
+                    }
+                },
+                _ if arg.starts_with("--") => return Err(Msg::UnknownOption(arg).to_string()),
+                _ => inputs.push(arg.clone()),
+            }
+        }
+        // verify 需要原文件与压缩文件两个路径，其余命令只需要一个
+        let expected = if matches!(command, Some(Command::Verify)) { 2 } else { 1 };
+        if let Some(extra) = inputs.get(expected) {
+            return Err(Msg::ExtraArgument(extra).to_string());
+        }
+        if inputs.len() < expected {
+            return Err(Msg::MissingInput.to_string());
+        }
+        let archive = inputs.get(1).cloned();
+        let input = inputs.swap_remove(0);
+        Ok(Self {
+            command,
+            input,
+            archive,
+            output,
+            stats,
+            threads,
+            dump_tree,
+            no_clobber,
+            remove,
+            estimate,
+            max_symbols,
+            sample,
+            quiet,
+            table,
+        })
+    }
+
+    fn run(&self) -> Result<(), HuffmanError> {
+        let command = match self.command {
+            Some(command) => command,
+            None => detect_command(&self.input)?,
+        };
+        let report = Reporter::new(self.quiet);
+        match command {
+            Command::Compress if self.estimate => hfm_estimate(&self.input, &report),
+            Command::Compress => {
+                let output = self.output.clone().unwrap_or_else(|| compressed_file_name(&self.input));
+                self.check_clobber(&output)?;
+                let is_dir = Path::new(&self.input).is_dir();
+                let table = self.table.as_deref().map(load_table).transpose()?;
+                match (&table, self.threads, self.sample) {
+                    (Some(table), _, _) => hfm_compress_table(&self.input, &output, table, &report)?,
+                    (None, Some(threads), _) if !is_dir => hfm_compress_blocks(&self.input, &output, threads, &report)?,
+                    (None, None, Some(sample)) if !is_dir => hfm_compress_sampled(&self.input, &output, sample, &report)?,
+                    _ => {
+                        let dump_tree = self.dump_tree.as_deref();
+                        hfm_compress(&self.input, &output, self.stats, dump_tree, self.max_symbols, &report)?
+                    },
+                }
+                self.remove_input(&self.input, &output, table.as_ref(), &report)
+            },
+            Command::Decompress => {
+                let output = self.output.clone().unwrap_or_else(|| decompressed_file_name(&self.input));
+                self.check_clobber(&output)?;
+                let table = self.table.as_deref().map(load_table).transpose()?;
+                match (&table, self.threads) {
+                    (Some(table), _) => hfm_decompress_table(&self.input, &output, table, &report)?,
+                    (None, Some(threads)) => hfm_decompress_blocks(&self.input, &output, threads, &report)?,
+                    (None, None) => hfm_decompress(&self.input, &output, &report)?,
+                }
+                self.remove_input(&output, &self.input, table.as_ref(), &report)
+            },
+            Command::Verify => {
+                let archive = self.archive.as_deref().unwrap_or_default();
+                let table = self.table.as_deref().map(load_table).transpose()?;
+                if !hfm_verify(&self.input, archive, table.as_ref(), &report)? {
+                    eprintln!("{}", Msg::VerifyFailed { archive, original: &self.input });
+                    exit(1);
+                }
+                Ok(())
+            },
+            Command::Stats => hfm_stats(&self.input),
+            Command::BuildTable => {
+                let output = self.output.as_deref().unwrap_or(DEFAULT_TABLE_FILE);
+                self.check_clobber(output)?;
+                hfm_build_table(&self.input, output, &report)
+            },
+            Command::Help => {
+                println!("{}", Msg::Usage);
+                Ok(())
+            },
+        }
+    }
+
+    /// 指定了 `--rm` 时，先校验 `archive` 能还原出 `original`，通过后才删除输入文件
+    ///
+    /// 目录与标准输入 / 输出无法校验，保留输入并给出提示；校验失败时保留输入并以非零状态退出。
+    /// 使用共享编码表压缩时，`table` 为同一编码表
+    fn remove_input(&self, original: &str, archive: &str, table: Option<&CodeTable>, report: &Reporter) -> Result<(), HuffmanError> {
+        if !self.remove {
+            return Ok(());
+        }
+        let input = &self.input;
+        if [original, archive].iter().any(|path| *path == STDIO || Path::new(path).is_dir()) {
+            report.line(Msg::InputKept(input));
+            return Ok(());
+        }
+        if !hfm_verify(original, archive, table, report)? {
+            eprintln!("{}", Msg::VerifyFailed { archive, original });
+            exit(1);
+        }
+        fs::remove_file(input)?;
+        report.line(Msg::InputRemoved(input));
+        Ok(())
+    }
+
+    /// 指定了 `--no-clobber` 且输出路径已存在时报错，文件与目录都不覆盖
+    fn check_clobber(&self, output: &str) -> Result<(), HuffmanError> {
+        if self.no_clobber && output != STDIO && Path::new(output).exists() {
+            let msg = Msg::OutputExists(output).to_string();
+            return Err(io::Error::new(io::ErrorKind::AlreadyExists, msg).into());
+        }
+        Ok(())
+    }
+}
 
 fn main() {
-    println!("【哈夫曼压缩】");
+    let args = env::args().skip(1).collect::<Vec<_>>();
+    if args.is_empty() {
+        interactive();
+        return;
+    }
+    let args = match Args::parse(&args) {
+        Ok(args) => args,
+        Err(e) => {
+            eprintln!("{}\n\n{}", e, Msg::Usage);
+            exit(2);
+        }
+    };
+    if let Err(e) = args.run() {
+        eprintln!("{}", Msg::Failed(&e));
+        exit(1);
+    }
+}
+
+/// 交互模式
+fn interactive() {
+    println!("{}", Msg::InteractiveTitle);
     loop {
-        println!("1. 压缩文件\t2. 解压文件\t3. 退出");
+        println!("{}", Msg::InteractiveMenu);
         match read().as_str() {
             "1" => {
-                println!("待压缩文件路径名：");
+                println!("{}", Msg::AskCompressPath);
                 let file = read();
-                hfm_compress(&file);
-                break;
+                match hfm_compress(&file, &compressed_file_name(&file), false, None, None, &Reporter::new(false)) {
+                    Ok(()) => break,
+                    Err(e) => println!("{}", Msg::CompressFailed(&e)),
+                }
             },
             "2" => {
-                println!("待解压文件路径名：");
+                println!("{}", Msg::AskDecompressPath);
                 let file = read();
-                let config_file = format!("{}.config", file);
 
-                println!("请输入保存文件路径：");
+                println!("{}", Msg::AskSavePath);
                 let save_file = read();
-                hfm_decompress(&file, &config_file, &save_file);
-                break;
+                match hfm_decompress(&file, &save_file, &Reporter::new(false)) {
+                    Ok(()) => break,
+                    Err(e) => println!("{}", Msg::DecompressFailed(&e)),
+                }
             },
             "3" => exit(1),
             _ => ()
@@ -31,6 +286,30 @@ fn main() {
     }
 }
 
+/// 根据扩展名和文件开头的魔数判断应当压缩还是解压
+fn detect_command(file: &str) -> Result<Command, HuffmanError> {
+    if Path::new(file).is_dir() {
+        return Ok(Command::Compress);
+    }
+    if file.ends_with(".hfm") {
+        return Ok(Command::Decompress);
+    }
+    let is_container = if file == STDIO {
+        // 只查看标准输入已缓冲的数据，不消耗输入
+        HuffmanCodec::is_container(io::stdin().lock().fill_buf()?)
+    } else {
+        HuffmanCodec::is_container(&file_head(file, 4)?)
+    };
+    Ok(if is_container { Command::Decompress } else { Command::Compress })
+}
+
+/// 文件开头至多 `len` 字节
+fn file_head(file: &str, len: u64) -> io::Result<Vec<u8>> {
+    let mut head = Vec::with_capacity(len as usize);
+    File::open(file)?.take(len).read_to_end(&mut head)?;
+    Ok(head)
+}
+
 /// 读取终端输入
 fn read() -> String {
     let mut cmd = String::new();
@@ -40,56 +319,448 @@ fn read() -> String {
     cmd.trim().to_string()
 }
 
-fn hfm_compress(file: &str) {
-    let filename = {
-        let mut arr = file.split(".").collect::<Vec<&str>>();
-        arr.pop();
-        arr.join(".")
-    };
-    // 打开文件并读取字符串到内存中
-    let mut input_file = File::open(file).expect("未找到该文件");
-    let mut source_buf = String::new();
-    input_file.read_to_string(&mut source_buf).expect("读取文件失败");
-
-    // 哈夫曼编码
-    let (u8_arr, config) = HuffmanCodec::encode(&source_buf);
-
-    // 创建压缩文件
-    let output_file_name = format!("{}.hfm", filename);
-    let mut output_file = File::create(&output_file_name).unwrap();
-    output_file.write(&u8_arr).unwrap();
-
-    // 创建压缩配置文件
-    let output_cfg_file_name = format!("{}.hfm.config", filename);
-    let mut output_cfg_file = File::create(&output_cfg_file_name).unwrap();
-    output_cfg_file.write(config.as_bytes()).unwrap();
-    println!("\n压缩成功！\n文件保存为: {}\n配置文件: {}", output_file_name, output_cfg_file_name);
-    let size_before = source_buf.as_bytes().len();
-    let size_after = u8_arr.len();
-    println!("压缩前大小：{} 字节", size_before);
-    println!("压缩后大小：{} 字节", size_after);
-    println!("压缩比率： {:.2}%", ((size_after as f64 / size_before as f64) * 100.0));
-}
-
-fn hfm_decompress(file: &str, config_file: &str, save_file: &str) {
-    // 压缩文件
-    let mut encodede_file = File::open(file).expect(format!("未找到文件:{}",file).as_str());
-    let mut buf = vec![];
-    encodede_file.read_to_end(&mut buf).unwrap();
-
-    // 读取配置文件
-    let mut config = File::open(config_file).expect(format!("未找到配置文件:{}", config_file).as_str());
-    let mut buf2 = String::new();
-    config.read_to_string(&mut buf2).unwrap();
-    
-    // 构建配置
-    let char_map = DecodeConfig::build(&buf2);
-
-    // 解码
-    let result = HuffmanCodec::decode(&buf, &char_map);
-
-    let mut savef = File::create(save_file).unwrap();
-    savef.write(result.as_bytes()).unwrap();
-
-    println!("\n解压成功！\n文件已保存至：{}", save_file);
+/// 压缩文件的默认保存路径：只把最后一个扩展名换成 `.hfm`，没有扩展名或为目录时直接加上 `.hfm`，
+/// 从标准输入读取时写到标准输出
+fn compressed_file_name(file: &str) -> String {
+    if file == STDIO {
+        return STDIO.to_owned();
+    }
+    let path = Path::new(file);
+    if path.is_dir() {
+        return format!("{}.hfm", file.trim_end_matches('/'));
+    }
+    path.with_extension("hfm").to_string_lossy().into_owned()
+}
+
+/// 解压文件的默认保存路径：去掉 `.hfm` 扩展名，没有该扩展名时加上 `.out`，
+/// 从标准输入读取时写到标准输出
+///
+/// 压缩文件头记录了原文件名时，输出到压缩文件所在目录下的同名文件
+fn decompressed_file_name(file: &str) -> String {
+    if file == STDIO {
+        return STDIO.to_owned();
+    }
+    // 只接受不含目录的文件名，且不能与压缩文件本身同名
+    let recorded = File::open(file).ok()
+        .and_then(|input| HuffmanCodec::read_meta(BufReader::new(input)).ok())
+        .and_then(|meta| meta.filename)
+        .filter(|name| Path::new(name).file_name() == Some(OsStr::new(name)))
+        .map(|name| Path::new(file).with_file_name(name))
+        .filter(|path| path != Path::new(file));
+    if let Some(path) = recorded {
+        return path.to_string_lossy().into_owned();
+    }
+    match file.strip_suffix(".hfm") {
+        Some(name) if !name.is_empty() => name.to_owned(),
+        _ => format!("{}.out", file),
+    }
+}
+
+/// 表示标准输入 / 标准输出的路径
+const STDIO: &str = "-";
+
+/// 可回到开头重新读取的输入
+trait ReadSeek: Read + Seek {}
+
+impl<T: Read + Seek> ReadSeek for T {}
+
+/// 打开压缩的输入，标准输入无法回退，先整体读入内存
+fn open_seekable_input(file: &str) -> Result<Box<dyn ReadSeek>, HuffmanError> {
+    if file == STDIO {
+        let mut buf = vec![];
+        io::stdin().lock().read_to_end(&mut buf)?;
+        return Ok(Box::new(Cursor::new(buf)));
+    }
+    Ok(Box::new(BufReader::new(File::open(file)?)))
+}
+
+fn open_input(file: &str) -> Result<Box<dyn Read>, HuffmanError> {
+    if file == STDIO {
+        return Ok(Box::new(io::stdin().lock()));
+    }
+    Ok(Box::new(BufReader::new(File::open(file)?)))
+}
+
+fn open_output(file: &str) -> Result<Box<dyn Write>, HuffmanError> {
+    if file == STDIO {
+        return Ok(Box::new(io::stdout().lock()));
+    }
+    Ok(Box::new(BufWriter::new(File::create(file)?)))
+}
+
+/// 把写入的数据与原文件逐字节比较，不保存写入的数据
+struct CompareWriter<R: Read> {
+    original: R,
+    buf: Vec<u8>,
+    /// 目前为止写入的数据是否都与原文件相同
+    equal: bool,
+}
+
+impl<R: Read> CompareWriter<R> {
+    fn new(original: R) -> Self {
+        Self { original, buf: vec![], equal: true }
+    }
+    /// 写入结束后，原文件也必须恰好读完
+    fn finish(mut self) -> io::Result<bool> {
+        Ok(self.equal && self.original.read(&mut [0u8])? == 0)
+    }
+}
+
+impl<R: Read> Write for CompareWriter<R> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        if self.equal {
+            self.buf.resize(buf.len(), 0);
+            match self.original.read_exact(&mut self.buf) {
+                Ok(()) => self.equal = self.buf == buf,
+                // 原文件比解压结果短
+                Err(e) if e.kind() == io::ErrorKind::UnexpectedEof => self.equal = false,
+                Err(e) => return Err(e),
+            }
+        }
+        Ok(buf.len())
+    }
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+/// 输出状态信息，总是写到标准错误，标准输出只用于写出压缩 / 解压的数据；指定了 `--quiet` 时不输出
+struct Reporter {
+    quiet: bool,
+}
+
+impl Reporter {
+    fn new(quiet: bool) -> Self {
+        Self { quiet }
+    }
+    /// 状态信息中显示的输出路径
+    fn path(&self, path: &str) -> String {
+        if path == STDIO { Msg::Stdout.to_string() } else { path.to_owned() }
+    }
+    fn line(&self, msg: Msg) {
+        if !self.quiet {
+            eprintln!("{}", msg);
+        }
+    }
+    /// 写到标准输出的结果，例如 `--estimate` 的预估大小，同样受 `--quiet` 控制
+    fn print(&self, msg: Msg) {
+        if !self.quiet {
+            println!("{}", msg);
+        }
+    }
+}
+
+/// 在标准错误上绘制压缩进度，标准错误不是终端、文件较小或指定了 `--quiet` 时不显示
+struct ProgressBar {
+    enabled: bool,
+    percent: Option<u64>,
+}
+
+impl ProgressBar {
+    /// 小于该大小的文件瞬间就能处理完，不显示进度
+    const MIN_SIZE: u64 = 4 * 1024 * 1024;
+    const WIDTH: u64 = 40;
+
+    fn new(report: &Reporter) -> Self {
+        Self { enabled: !report.quiet && io::stderr().is_terminal(), percent: None }
+    }
+    fn update(&mut self, done: u64, total: u64) {
+        // total 为两遍扫描的总字节数
+        if !self.enabled || total < Self::MIN_SIZE * 2 {
+            return;
+        }
+        let percent = done * 100 / total;
+        // 百分比变化时才重绘
+        if self.percent == Some(percent) {
+            return;
+        }
+        self.percent = Some(percent);
+        let filled = (percent * Self::WIDTH / 100) as usize;
+        eprint!("\r[{:<width$}] {:>3}%", "#".repeat(filled), percent, width = Self::WIDTH as usize);
+    }
+    fn finish(&self) {
+        if self.percent.is_some() {
+            eprintln!();
+        }
+    }
+}
+
+fn hfm_compress(
+    file: &str,
+    output_file_name: &str,
+    stats: bool,
+    dump_tree: Option<&str>,
+    max_symbols: Option<usize>,
+    report: &Reporter,
+) -> Result<(), HuffmanError> {
+    if file != STDIO && Path::new(file).is_dir() {
+        // 目录整体读入内存打包压缩
+        let archive = ByteHuffmanCodec::encode_dir(Path::new(file))?;
+        let mut output = open_output(output_file_name)?;
+        output.write_all(&archive)?;
+        output.flush()?;
+        report.line(Msg::DirCompressed { dir: file, output: &report.path(output_file_name) });
+        report.line(Msg::CompressedSize(archive.len()));
+        return Ok(());
+    }
+    // 逐块读取并编码，配置与压缩数据写入同一个文件
+    let mut input = open_seekable_input(file)?;
+    let output = open_output(output_file_name)?;
+    let mut bar = ProgressBar::new(report);
+    let max_symbols = max_symbols.unwrap_or(usize::MAX);
+    let meta = file_meta(file);
+    let (sizes, weights) = HuffmanCodec::encode_reader_with_weights(&mut input, output, meta, max_symbols, |done, total| {
+        bar.update(done, total)
+    })?;
+    bar.finish();
+
+    report.line(Msg::FileCompressed { output: &report.path(output_file_name) });
+    report.line(Msg::OriginalSize(sizes.original));
+    report.line(Msg::CompressedSizeWithConfig { total: sizes.compressed, config: sizes.config_size });
+    report.line(Msg::Ratio(sizes.ratio));
+    if sizes.compressed > sizes.original {
+        // 哈夫曼编码加上配置不比原文小时已自动原样保存，多出的只有文件头
+        report.line(Msg::LargerThanInput(sizes.compressed - sizes.original));
+    }
+
+    // 以下只输出统计信息或导出树，都不需要时不必再建树
+    if report.quiet && dump_tree.is_none() {
+        return Ok(());
+    }
+    // 与香农熵比较，查看编码距离理论极限有多近
+    let tree = HuffmanTree::build(weights.clone())?;
+    let bit_map = HuffmanBinaryMap::build(&tree)?;
+    report.line(Msg::Entropy { minimum: weights.entropy(), actual: bit_map.average_code_length(&weights) });
+    if let Some(path) = dump_tree {
+        fs::write(path, tree.borrow().to_dot())?;
+        report.line(Msg::TreeDumped { path });
+    }
+    if stats {
+        let lengths = bit_map.length_stats();
+        report.line(Msg::TreeHeight(tree.borrow().height()));
+        report.line(Msg::CodeLengths { min: lengths.min, max: lengths.max, average: lengths.average });
+    }
+    Ok(())
+}
+
+fn hfm_decompress(file: &str, save_file: &str, report: &Reporter) -> Result<(), HuffmanError> {
+    if file != STDIO {
+        let header = file_head(file, 8)?;
+        let legacy_config = format!("{}.config", file);
+        if !HuffmanCodec::is_container(&header) && Path::new(&legacy_config).is_file() {
+            return hfm_decompress_legacy(file, &legacy_config, save_file, report);
+        }
+        // 输入是配置，或输出路径才是压缩文件时，多半是两个路径填反了，在创建输出文件之前报错以免覆盖压缩文件
+        let output_is_archive =
+            save_file != STDIO && file_head(save_file, 4).is_ok_and(|head| HuffmanCodec::is_container(&head));
+        if !HuffmanCodec::is_container(&header) && (HuffmanCodec::is_config(&header) || output_is_archive) {
+            return Err(HuffmanError::SwappedInputs);
+        }
+        if save_file != STDIO && ByteHuffmanCodec::is_dir_archive(&header) {
+            ByteHuffmanCodec::decode_dir(&fs::read(file)?, Path::new(save_file))?;
+            report.line(Msg::DirDecompressed { output: save_file });
+            return Ok(());
+        }
+    }
+    // 逐块读取压缩文件并解码写出
+    let encodede_file = open_input(file)?;
+    let savef = open_output(save_file)?;
+    if let Err(e) = HuffmanCodec::decode_reader(encodede_file, savef) {
+        // 解压失败时删除写了一半的文件
+        if save_file != STDIO {
+            let _ = fs::remove_file(save_file);
+        }
+        return Err(e);
+    }
+    if file != STDIO && save_file != STDIO {
+        restore_mtime(file, save_file)?;
+    }
+
+    report.line(Msg::FileDecompressed { output: &report.path(save_file) });
+    Ok(())
+}
+
+/// 解压旧版的两文件格式：压缩数据不带文件头，配置单独保存在 `<压缩文件>.config` 中
+fn hfm_decompress_legacy(file: &str, config_file: &str, save_file: &str, report: &Reporter) -> Result<(), HuffmanError> {
+    let config = DecodeConfig::build(&fs::read_to_string(config_file)?)?;
+    let text = HuffmanCodec::decode(&fs::read(file)?, &config)?;
+    let mut output = open_output(save_file)?;
+    output.write_all(text.as_bytes())?;
+    output.flush()?;
+    report.line(Msg::FileDecompressed { output: &report.path(save_file) });
+    Ok(())
+}
+
+/// 输入文件的文件名与修改时间，记录在压缩文件头中，从标准输入压缩时不记录
+fn file_meta(file: &str) -> ContainerMeta {
+    if file == STDIO {
+        return ContainerMeta::default();
+    }
+    let filename = Path::new(file).file_name().and_then(OsStr::to_str).map(str::to_owned);
+    let mtime = fs::metadata(file)
+        .and_then(|metadata| metadata.modified())
+        .ok()
+        .and_then(|time| time.duration_since(UNIX_EPOCH).ok())
+        .map(|since| since.as_secs());
+    ContainerMeta { filename, mtime }
+}
+
+/// 压缩文件头记录了修改时间时，把解压出的文件的修改时间设为原文件的修改时间
+fn restore_mtime(archive: &str, save_file: &str) -> Result<(), HuffmanError> {
+    if let Some(mtime) = HuffmanCodec::read_meta(BufReader::new(File::open(archive)?))?.mtime {
+        File::options().write(true).open(save_file)?.set_modified(UNIX_EPOCH + Duration::from_secs(mtime))?;
+    }
+    Ok(())
+}
+
+/// 分块压缩时每块的大小，块越大压缩率越接近整体压缩，块越多越能利用多个线程
+const BLOCK_SIZE: usize = 1024 * 1024;
+
+/// 把整个文件读入内存，分块后由 `threads` 个线程并行压缩
+fn hfm_compress_blocks(file: &str, output_file_name: &str, threads: usize, report: &Reporter) -> Result<(), HuffmanError> {
+    let mut source = vec![];
+    open_input(file)?.read_to_end(&mut source)?;
+    let archive = ByteHuffmanCodec::encode_blocks(&source, BLOCK_SIZE, threads)?;
+    let mut output = open_output(output_file_name)?;
+    output.write_all(&archive)?;
+    output.flush()?;
+    report.line(Msg::FileCompressed { output: &report.path(output_file_name) });
+    report.line(Msg::OriginalSize(source.len()));
+    report.line(Msg::CompressedSizeWithBlocks { total: archive.len(), blocks: source.len().div_ceil(BLOCK_SIZE) });
+    report.line(Msg::Ratio(compression_ratio(source.len(), archive.len())));
+    Ok(())
+}
+
+/// 只用开头 `sample` 字节建立编码表，一遍读完输入，从标准输入压缩时也不必先读入内存
+fn hfm_compress_sampled(file: &str, output_file_name: &str, sample: usize, report: &Reporter) -> Result<(), HuffmanError> {
+    let output = open_output(output_file_name)?;
+    let sizes = HuffmanCodec::encode_sampled_with_meta(open_input(file)?, output, sample, file_meta(file))?;
+    report.line(Msg::FileCompressed { output: &report.path(output_file_name) });
+    report.line(Msg::OriginalSize(sizes.original));
+    report.line(Msg::CompressedSizeWithConfig { total: sizes.compressed, config: sizes.config_size });
+    report.line(Msg::Ratio(sizes.ratio));
+    Ok(())
+}
+
+/// 把整个压缩文件读入内存，由 `threads` 个线程并行解压各块，不分块的压缩文件同样可以解压
+fn hfm_decompress_blocks(file: &str, save_file: &str, threads: usize, report: &Reporter) -> Result<(), HuffmanError> {
+    let mut archive = vec![];
+    open_input(file)?.read_to_end(&mut archive)?;
+    if ByteHuffmanCodec::is_dir_archive(&archive) && save_file != STDIO {
+        ByteHuffmanCodec::decode_dir(&archive, Path::new(save_file))?;
+        report.line(Msg::DirDecompressed { output: save_file });
+        return Ok(());
+    }
+    let result = ByteHuffmanCodec::decode_blocks(&archive, threads)?;
+    let mut output = open_output(save_file)?;
+    output.write_all(&result)?;
+    output.flush()?;
+    report.line(Msg::FileDecompressed { output: &report.path(save_file) });
+    Ok(())
+}
+
+/// `build-table` 未指定输出路径时保存编码表的文件
+const DEFAULT_TABLE_FILE: &str = "table.json";
+
+/// 统计语料的字符权重，生成可由 `--table` 共用的编码表
+///
+/// 输出路径以 `.json` 结尾时保存为 JSON，否则为与配置中的权重表相同的文本格式
+fn hfm_build_table(corpus: &str, table_file: &str, report: &Reporter) -> Result<(), HuffmanError> {
+    let table = CodeTable::from_weights(CharWeightMap::from_reader(open_input(corpus)?)?)?;
+    let text = if table_file.ends_with(".json") { table.to_json() } else { table.to_text() };
+    let mut output = open_output(table_file)?;
+    output.write_all(text.as_bytes())?;
+    output.flush()?;
+    report.line(Msg::TableBuilt { path: &report.path(table_file), symbols: table.bit_map().inner.len() });
+    Ok(())
+}
+
+/// 读取 `build-table` 生成的编码表，JSON 与文本格式都可以
+fn load_table(file: &str) -> Result<CodeTable, HuffmanError> {
+    let source = fs::read_to_string(file)?;
+    if source.trim_start().starts_with('{') {
+        CodeTable::from_json(&source)
+    } else {
+        CodeTable::from_text(&source)
+    }
+}
+
+/// 用共享编码表压缩文本，不再统计权重，压缩文件中只记录编码表编号
+///
+/// 文件中有编码表里没有的字符时报错，不创建输出文件
+fn hfm_compress_table(file: &str, output_file_name: &str, table: &CodeTable, report: &Reporter) -> Result<(), HuffmanError> {
+    let mut source = vec![];
+    open_input(file)?.read_to_end(&mut source)?;
+    let source = String::from_utf8(source).map_err(|_| HuffmanError::InvalidUtf8)?;
+    let archive = HuffmanCodec::with_table(table).encode(&source)?;
+    let mut output = open_output(output_file_name)?;
+    output.write_all(&archive)?;
+    output.flush()?;
+    report.line(Msg::FileCompressed { output: &report.path(output_file_name) });
+    report.line(Msg::OriginalSize(source.len()));
+    report.line(Msg::CompressedSize(archive.len()));
+    report.line(Msg::Ratio(compression_ratio(source.len(), archive.len())));
+    Ok(())
+}
+
+/// 用压缩时的共享编码表解压，编码表不符时报错
+fn hfm_decompress_table(file: &str, save_file: &str, table: &CodeTable, report: &Reporter) -> Result<(), HuffmanError> {
+    let mut archive = vec![];
+    open_input(file)?.read_to_end(&mut archive)?;
+    let text = HuffmanCodec::with_table(table).decode(&archive)?;
+    let mut output = open_output(save_file)?;
+    output.write_all(text.as_bytes())?;
+    output.flush()?;
+    report.line(Msg::FileDecompressed { output: &report.path(save_file) });
+    Ok(())
+}
+
+/// 只统计权重并算出压缩后的大小，不写出任何文件
+fn hfm_estimate(file: &str, report: &Reporter) -> Result<(), HuffmanError> {
+    let sizes = HuffmanCodec::estimate_reader(open_input(file)?)?;
+    report.print(Msg::Estimated);
+    report.print(Msg::OriginalSize(sizes.original));
+    report.print(Msg::CompressedSizeWithConfig { total: sizes.compressed, config: sizes.config_size });
+    report.print(Msg::Ratio(sizes.ratio));
+    Ok(())
+}
+
+/// 输出每个字符的频率与编码，按频率从高到低排列，不写出任何文件
+fn hfm_stats(file: &str) -> Result<(), HuffmanError> {
+    let weights = CharWeightMap::from_reader(open_input(file)?)?;
+    let bit_map = HuffmanBinaryMap::build(&HuffmanTree::build(weights.clone())?)?;
+    let mut rows = weights.iter().collect::<Vec<_>>();
+    rows.sort_by(|a, b| b.1.cmp(a.1).then(a.0.cmp(b.0)));
+    println!("{}", Msg::StatsHeader);
+    for (ch, weight) in rows {
+        let code = bit_map.get(ch).unwrap_or_default();
+        let bits = code.iter().map(|&bit| if bit { '1' } else { '0' }).collect::<String>();
+        // 控制字符与空白显示为码位
+        let symbol = if ch.is_control() || ch.is_whitespace() { format!("U+{:04X}", *ch as u32) } else { ch.to_string() };
+        println!("{}\t{}\t{}\t{}", symbol, weight, bits, code.len());
+    }
+    println!("{}", Msg::Entropy { minimum: weights.entropy(), actual: bit_map.average_code_length(&weights) });
+    Ok(())
+}
+
+/// 流式解压压缩文件并与原文件比较，不写出任何文件
+///
+/// 引用共享编码表的压缩文件需提供 `table`，此时整个压缩文件读入内存后解压
+fn hfm_verify(original: &str, archive: &str, table: Option<&CodeTable>, report: &Reporter) -> Result<bool, HuffmanError> {
+    let mut compare = CompareWriter::new(open_input(original)?);
+    match table {
+        Some(table) => {
+            let mut bytes = vec![];
+            open_input(archive)?.read_to_end(&mut bytes)?;
+            compare.write_all(HuffmanCodec::with_table(table).decode(&bytes)?.as_bytes())?;
+        },
+        None => {
+            HuffmanCodec::decode_reader(open_input(archive)?, &mut compare)?;
+        },
+    }
+    let equal = compare.finish()?;
+    if equal {
+        report.line(Msg::VerifyPassed { archive, original });
+    }
+    Ok(equal)
 }
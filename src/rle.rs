@@ -0,0 +1,83 @@
+//! 游程编码，在哈夫曼编码之前压缩连续重复的字节
+//!
+//! 连续出现两个相同的字节时，其后紧跟一个字节记录之后还有多少个该字节（`0..=255`），
+//! 因此一段游程最长 257 字节，更长的游程拆成多段；不重复的字节原样保留。
+//! 由 [`ByteHuffmanCodec::encode_rle_then_huffman`] 使用，压缩文件头中设置 `FLAG_RLE`。
+
+use std::io::{self, Write};
+
+use crate::huffman::HuffmanError;
+
+/// 一段游程最多的字节数：两个相同的字节 + 计数的最大值
+const MAX_RUN: usize = 2 + u8::MAX as usize;
+
+/// 游程编码
+pub(crate) fn encode(source: &[u8]) -> Vec<u8> {
+    let mut result = Vec::with_capacity(source.len());
+    let mut rest = source;
+    while let Some(&byte) = rest.first() {
+        let run = rest.iter().take(MAX_RUN).take_while(|b| **b == byte).count();
+        result.push(byte);
+        if run >= 2 {
+            result.push(byte);
+            result.push((run - 2) as u8);
+        }
+        rest = &rest[run..];
+    }
+    result
+}
+
+/// 还原 [`encode`] 的结果，最后两个相同的字节之后缺少计数时返回 `UnexpectedEof`
+pub(crate) fn decode(data: &[u8]) -> Result<Vec<u8>, HuffmanError> {
+    let mut writer = RleWriter::new(Vec::with_capacity(data.len()));
+    writer.write_all(data)?;
+    writer.finish()
+}
+
+/// 边写入边还原游程编码，写入的数据可在任意位置切分
+pub(crate) struct RleWriter<W: Write> {
+    inner: W,
+    buf: Vec<u8>,
+    /// 上一个字节，刚写完一段游程时为 `None`
+    prev: Option<u8>,
+    /// 下一个字节是游程的计数
+    count_next: bool,
+}
+
+impl<W: Write> RleWriter<W> {
+    pub(crate) fn new(inner: W) -> Self {
+        Self { inner, buf: vec![], prev: None, count_next: false }
+    }
+
+    /// 结束写入并返回内层，游程缺少计数时返回 `UnexpectedEof`
+    pub(crate) fn finish(mut self) -> Result<W, HuffmanError> {
+        if self.count_next {
+            return Err(HuffmanError::UnexpectedEof);
+        }
+        self.inner.flush()?;
+        Ok(self.inner)
+    }
+}
+
+impl<W: Write> Write for RleWriter<W> {
+    fn write(&mut self, data: &[u8]) -> io::Result<usize> {
+        self.buf.clear();
+        for &byte in data {
+            if self.count_next {
+                let prev = self.prev.take().unwrap();
+                self.buf.resize(self.buf.len() + byte as usize, prev);
+                self.count_next = false;
+            } else {
+                self.buf.push(byte);
+                self.count_next = self.prev == Some(byte);
+                self.prev = Some(byte);
+            }
+        }
+        self.inner.write_all(&self.buf)?;
+        Ok(data.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+}
@@ -0,0 +1,529 @@
+//! 流式编解码，按块读写，内存占用与文件大小无关
+
+use std::io::{self, BufRead, Read, Seek, SeekFrom, Write};
+
+use crate::bits::{read_some, BitReader, BitWriter};
+use crate::block::{read_block, read_block_index};
+use crate::bytes::ByteHuffmanCodec;
+use crate::container::{symbols_to_bytes, CompressionReport, ContainerHeader, ContainerMeta, FLAG_FOOTER};
+use crate::crc32::Crc32;
+use crate::huffman::{BitDecoder, CharWeightMap, HuffmanBinaryMap, HuffmanCodec, HuffmanError, HuffmanTree, ESCAPE_BITS};
+use crate::rle::RleWriter;
+
+/// 每次读取的块大小
+const CHUNK_SIZE: usize = 64 * 1024;
+
+/// 流式解码的结果，由 [`HuffmanCodec::decode_reader`] 返回
+///
+/// 多个压缩文件首尾相接保存时，可据 `bytes_consumed` 找到下一个压缩文件的开头
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DecodeOutcome {
+    /// 这个压缩文件占用的字节数，包括文件头、配置与压缩数据
+    pub bytes_consumed: u64,
+    /// 解码出的符号数量，原样保存的压缩文件为原文的字节数
+    pub symbols: u64,
+}
+
+impl HuffmanCodec {
+    /// 流式哈夫曼编码，输出与 [`encode_to_container`](Self::encode_to_container) 相同格式的压缩文件
+    ///
+    /// 第一遍读取统计字符权重，第二遍回到起始位置逐块编码写出，因此 `reader` 需要支持 `Seek`。
+    /// 返回压缩结果的大小统计
+    pub fn encode_reader<R: Read + Seek, W: Write>(reader: R, writer: W) -> Result<CompressionReport, HuffmanError> {
+        Self::encode_reader_with_progress(reader, writer, |_, _| ())
+    }
+
+    /// 与 [`encode_reader`](Self::encode_reader) 相同，并在处理过程中回调报告进度
+    ///
+    /// `progress(已处理字节数, 总字节数)` 中的总字节数为输入长度的两倍：
+    /// 第一遍统计权重时从 0 报告到输入长度，第二遍编码时再从输入长度报告到总字节数
+    pub fn encode_reader_with_progress<R, W, F>(reader: R, writer: W, progress: F) -> Result<CompressionReport, HuffmanError>
+    where
+        R: Read + Seek,
+        W: Write,
+        F: FnMut(u64, u64),
+    {
+        Self::encode_reader_with_max_symbols(reader, writer, usize::MAX, progress)
+    }
+
+    /// 与 [`encode_reader_with_progress`](Self::encode_reader_with_progress) 相同，
+    /// 但第一遍统计出的不同字符超过 `max_symbols` 个时返回 `AlphabetTooLarge`，不再建树与写出数据
+    pub fn encode_reader_with_max_symbols<R, W, F>(
+        reader: R,
+        writer: W,
+        max_symbols: usize,
+        progress: F,
+    ) -> Result<CompressionReport, HuffmanError>
+    where
+        R: Read + Seek,
+        W: Write,
+        F: FnMut(u64, u64),
+    {
+        Self::encode_reader_with_meta(reader, writer, ContainerMeta::default(), max_symbols, progress)
+    }
+
+    /// 与 [`encode_reader_with_max_symbols`](Self::encode_reader_with_max_symbols) 相同，
+    /// 并在文件头中记录原文件信息 `meta`
+    pub fn encode_reader_with_meta<R, W, F>(
+        reader: R,
+        writer: W,
+        meta: ContainerMeta,
+        max_symbols: usize,
+        progress: F,
+    ) -> Result<CompressionReport, HuffmanError>
+    where
+        R: Read + Seek,
+        W: Write,
+        F: FnMut(u64, u64),
+    {
+        Ok(Self::encode_reader_with_weights(reader, writer, meta, max_symbols, progress)?.0)
+    }
+
+    /// 与 [`encode_reader_with_meta`](Self::encode_reader_with_meta) 相同，并返回第一遍统计出的字符权重，
+    /// 可据此计算熵或重建哈夫曼树，不必再读一遍输入
+    pub fn encode_reader_with_weights<R, W, F>(
+        mut reader: R,
+        mut writer: W,
+        meta: ContainerMeta,
+        max_symbols: usize,
+        mut progress: F,
+    ) -> Result<(CompressionReport, CharWeightMap), HuffmanError>
+    where
+        R: Read + Seek,
+        W: Write,
+        F: FnMut(u64, u64),
+    {
+        let start = reader.stream_position()?;
+        let len = reader.seek(SeekFrom::End(0))? - start;
+        reader.seek(SeekFrom::Start(start))?;
+        let total = len * 2;
+        progress(0, total);
+
+        // 第一遍：统计字符权重
+        let mut weight_map = CharWeightMap::build("");
+        let mut capacity = 0;
+        let mut crc = Crc32::new();
+        read_utf8_chunks(&mut reader, |text| {
+            weight_map.update(text);
+            crc.update(text.as_bytes());
+            capacity += text.len();
+            progress(capacity as u64, total);
+            Ok(())
+        })?;
+        weight_map.check_alphabet(max_symbols)?;
+        // 根据权重和编码长度算出总位数，从而在写出数据前确定补位数量
+        let (bit_map, config, total_bits) = Self::plan_container(&weight_map, capacity)?;
+
+        // 压缩后不比原文小时原样保存
+        reader.seek(SeekFrom::Start(start))?;
+        if config.len() as u64 + total_bits.div_ceil(8) >= capacity as u64 {
+            let header = ContainerHeader::stored(crc.finish(), capacity as u64).with_meta(meta);
+            header.write(&mut writer)?;
+            let mut copy_progress = |n| progress(capacity as u64 + n, total);
+            copy_exact(&mut reader, &mut writer, capacity as u64, &mut Crc32::new(), &mut copy_progress)?;
+            return Ok((CompressionReport::new(capacity, header.size() + capacity, header.size()), weight_map));
+        }
+        let header = ContainerHeader::new(crc.finish(), config).with_meta(meta);
+        header.write(&mut writer)?;
+
+        // 第二遍：逐块编码写出
+        let mut bits = BitWriter::new(&mut writer);
+        let mut processed = capacity as u64;
+        read_utf8_chunks(&mut reader, |text| {
+            processed += text.len() as u64;
+            progress(processed, total);
+            for ch in text.chars() {
+                let code = bit_map.get(&ch)
+                    .ok_or_else(|| io::Error::other("文件在压缩过程中被修改"))?;
+                bits.write_bits(code)?;
+            }
+            Ok(())
+        })?;
+        bits.finish()?;
+        let compressed = header.size() + total_bits.div_ceil(8) as usize;
+        Ok((CompressionReport::new(capacity, compressed, header.size()).with_payload_bits(total_bits), weight_map))
+    }
+
+    /// 只用开头 `sample_bytes` 字节的样本统计字符权重，再把整个输入一遍编码写出
+    ///
+    /// 省去统计权重的第一遍读取，`reader` 不必支持 `Seek`。编码表中额外保留一个权重为 1 的转义符，
+    /// 样本之后才出现的字符写为转义符加上 21 位码点。写完数据才知道补位数量，因此 CRC32 与配置写在压缩数据之后
+    /// （同 [`encode_to_container_with_footer`](Self::encode_to_container_with_footer)），压缩后更大时也不会改为原样保存
+    pub fn encode_sampled<R: Read, W: Write>(reader: R, writer: W, sample_bytes: usize) -> Result<CompressionReport, HuffmanError> {
+        Self::encode_sampled_with_meta(reader, writer, sample_bytes, ContainerMeta::default())
+    }
+
+    /// 与 [`encode_sampled`](Self::encode_sampled) 相同，并在文件头中记录原文件信息 `meta`
+    pub fn encode_sampled_with_meta<R: Read, W: Write>(
+        mut reader: R,
+        mut writer: W,
+        sample_bytes: usize,
+        meta: ContainerMeta,
+    ) -> Result<CompressionReport, HuffmanError> {
+        let header = ContainerHeader::new(0, String::new()).with_flags(FLAG_FOOTER).with_meta(meta);
+        header.write(&mut writer)?;
+        let mut sample = CharWeightMap::build("");
+        let mut sampled = 0;
+        // 样本统计完之前读到的文本，建立编码表后再编码
+        let mut pending = String::new();
+        let mut codes: Option<SampledCodes> = None;
+        let (mut capacity, mut count, mut total_bits, mut crc) = (0, 0, 0, Crc32::new());
+        let mut bits = BitWriter::new(&mut writer);
+        read_utf8_chunks(&mut reader, |text| {
+            crc.update(text.as_bytes());
+            capacity += text.len();
+            count += text.chars().count();
+            if let Some(codes) = &codes {
+                total_bits += codes.write(&mut bits, text)?;
+                return Ok(());
+            }
+            // 样本截止在字符边界上
+            let mut take = text.len().min(sample_bytes - sampled);
+            while !text.is_char_boundary(take) {
+                take -= 1;
+            }
+            sample.update(&text[..take]);
+            sampled += take;
+            pending.push_str(text);
+            if take == text.len() && sampled < sample_bytes {
+                return Ok(());
+            }
+            let table = SampledCodes::new(&sample)?;
+            total_bits += table.write(&mut bits, &pending)?;
+            pending = String::new();
+            codes = Some(table);
+            Ok(())
+        })?;
+        // 输入不足一个样本
+        let codes = match codes {
+            Some(codes) => codes,
+            None => {
+                let codes = SampledCodes::new(&sample)?;
+                total_bits += codes.write(&mut bits, &pending)?;
+                codes
+            },
+        };
+        let space = bits.finish()?;
+        let mut config = Self::config_text(space, capacity, count, "weight", &codes.weights);
+        config.push_str(&format!("escape:U+{:04X}\n", codes.escape as u32));
+        let payload = total_bits.div_ceil(8) as usize;
+        // 压缩数据之后依次为 CRC32、配置长度、配置与偏移
+        let compressed = header.size() + payload + 16 + config.len();
+        let footer = ContainerHeader::new(crc.finish(), config).with_flags(FLAG_FOOTER);
+        footer.write_footer(&mut writer, (header.size() + payload) as u64)?;
+        writer.flush()?;
+        Ok(CompressionReport::new(capacity, compressed, compressed - payload).with_payload_bits(total_bits))
+    }
+
+    /// 逐块读取文本，预估 [`encode_reader`](Self::encode_reader) 的大小统计，不写出任何数据
+    ///
+    /// 只需读一遍，`reader` 不必支持 `Seek`
+    pub fn estimate_reader<R: Read>(mut reader: R) -> Result<CompressionReport, HuffmanError> {
+        let mut weight_map = CharWeightMap::build("");
+        let mut capacity = 0;
+        read_utf8_chunks(&mut reader, |text| {
+            weight_map.update(text);
+            capacity += text.len();
+            Ok(())
+        })?;
+        Self::estimate_with_weights(&weight_map, capacity)
+    }
+
+    /// 流式解码由 [`encode_reader`](Self::encode_reader) 或
+    /// [`encode_to_container`](Self::encode_to_container) 生成的压缩文件，
+    /// 也能解码 [`ByteHuffmanCodec`](crate::ByteHuffmanCodec) 生成的字节压缩文件
+    ///
+    /// 返回压缩文件占用的字节数与解码出的符号数量。按块读取时可能多读入压缩文件之后的数据，
+    /// 因此 `reader` 之后的内容需由调用方按 `bytes_consumed` 从原数据中定位，
+    /// 或改用 [`decode_exact`](Self::decode_exact)
+    pub fn decode_reader<R: Read, W: Write>(mut reader: R, writer: W) -> Result<DecodeOutcome, HuffmanError> {
+        let header = ContainerHeader::read(&mut reader)?;
+        if header.is_rle() {
+            let mut writer = RleWriter::new(writer);
+            let outcome = decode_stream(&header, reader, &mut writer)?;
+            writer.finish()?;
+            return Ok(outcome);
+        }
+        decode_stream(&header, reader, writer)
+    }
+
+    /// 与 [`decode_reader`](Self::decode_reader) 相同，但只读取压缩文件本身的字节
+    ///
+    /// 解出配置中记录的字符数量后立即停止，`reader` 恰好停在压缩文件之后，
+    /// 之后的内容（如外层容器的其余数据或按块对齐的填充）原样留给调用方继续读取
+    pub fn decode_exact<R: BufRead, W: Write>(reader: &mut R, writer: W) -> Result<DecodeOutcome, HuffmanError> {
+        let header = ContainerHeader::read(reader)?;
+        if header.is_rle() {
+            let mut writer = RleWriter::new(writer);
+            let outcome = decode_stream_exact(&header, reader, &mut writer)?;
+            writer.finish()?;
+            return Ok(outcome);
+        }
+        decode_stream_exact(&header, reader, writer)
+    }
+
+    /// 依次解码首尾相接保存的多个压缩文件，直到输入结束，返回每个压缩文件的原文
+    ///
+    /// 下一个压缩文件的开头由前一个的 `bytes_consumed` 确定，因此先把输入整体读入内存；
+    /// 输入为空时返回空数组，末尾不足一个完整的压缩文件时返回相应的错误
+    pub fn decode_all<R: Read>(mut reader: R) -> Result<Vec<Vec<u8>>, HuffmanError> {
+        let mut input = vec![];
+        reader.read_to_end(&mut input)?;
+        let mut rest = input.as_slice();
+        let mut frames = vec![];
+        while !rest.is_empty() {
+            let mut frame = vec![];
+            let outcome = Self::decode_reader(rest, &mut frame)?;
+            rest = rest.get(outcome.bytes_consumed as usize..).ok_or(HuffmanError::UnexpectedEof)?;
+            frames.push(frame);
+        }
+        Ok(frames)
+    }
+}
+
+impl CharWeightMap {
+    /// 逐块读取文本并统计字符权重
+    pub fn from_reader<R: Read>(mut reader: R) -> Result<Self, HuffmanError> {
+        let mut map = Self::build("");
+        read_utf8_chunks(&mut reader, |text| {
+            map.update(text);
+            Ok(())
+        })?;
+        Ok(map)
+    }
+}
+
+/// 抽样编码的编码表，样本中没有的字符写为转义符加码点
+struct SampledCodes {
+    bit_map: HuffmanBinaryMap,
+    /// 样本中没有的最小码点，权重为 1
+    escape: char,
+    /// 样本的字符权重加上转义符，记录在配置中
+    weights: CharWeightMap,
+}
+
+impl SampledCodes {
+    fn new(sample: &CharWeightMap) -> Result<Self, HuffmanError> {
+        let escape = (0..=char::MAX as u32)
+            .filter_map(char::from_u32)
+            .find(|ch| !sample.inner.contains_key(ch))
+            .expect("样本不可能包含所有字符");
+        let mut weights = sample.clone();
+        weights.inner.insert(escape, 1);
+        let bit_map = HuffmanBinaryMap::build(&HuffmanTree::build(weights.clone())?)?;
+        Ok(Self { bit_map, escape, weights })
+    }
+
+    /// 编码 `text` 并写出，返回写出的位数
+    fn write<W: Write>(&self, bits: &mut BitWriter<W>, text: &str) -> io::Result<u64> {
+        let escape = self.bit_map.get(&self.escape).unwrap();
+        let mut written = 0;
+        for ch in text.chars() {
+            match self.bit_map.get(&ch) {
+                Some(code) if ch != self.escape => {
+                    bits.write_bits(code)?;
+                    written += code.len() as u64;
+                },
+                _ => {
+                    bits.write_bits(escape)?;
+                    for i in (0..ESCAPE_BITS).rev() {
+                        bits.write_bit((ch as u32 >> i) & 1 != 0)?;
+                    }
+                    written += (escape.len() + usize::from(ESCAPE_BITS)) as u64;
+                },
+            }
+        }
+        Ok(written)
+    }
+}
+
+/// [`HuffmanCodec::decode_reader`] 读取文件头之后的部分，游程编码由调用方还原
+fn decode_stream<R: Read, W: Write>(header: &ContainerHeader, mut reader: R, mut writer: W) -> Result<DecodeOutcome, HuffmanError> {
+    if header.is_blocks() {
+        return decode_blocks(header, reader, writer);
+    }
+    if header.is_footer() {
+        return decode_footer(header, reader, writer);
+    }
+    let decode_map = header.decode_config(None)?;
+    let mut crc = Crc32::new();
+    if header.is_stored() {
+        let len = decode_map.capacity as u64;
+        copy_exact(&mut reader, &mut writer, len, &mut crc, &mut |_| ())?;
+        header.verify(crc.finish())?;
+        return Ok(DecodeOutcome { bytes_consumed: header.size() as u64 + len, symbols: len });
+    }
+    let mut decoder = BitDecoder::new(&decode_map);
+    let mut bits = BitReader::with_order(reader, decode_map.space, decode_map.order);
+    let mut out = String::with_capacity(CHUNK_SIZE);
+    while !decoder.is_done() {
+        let Some(bit) = bits.read_bit()? else { break };
+        if let Some(ch) = decoder.step(bit)? {
+            out.push(ch);
+            if out.len() >= CHUNK_SIZE {
+                write_decoded(&mut writer, &mut crc, &out, header.is_bytes())?;
+                out.clear();
+            }
+        }
+    }
+    decoder.finish()?;
+    write_decoded(&mut writer, &mut crc, &out, header.is_bytes())?;
+    writer.flush()?;
+    header.verify(crc.finish())?;
+    Ok(DecodeOutcome {
+        bytes_consumed: (header.size() + decoder.position().div_ceil(8)) as u64,
+        symbols: decoder.symbols() as u64,
+    })
+}
+
+/// [`HuffmanCodec::decode_exact`] 读取文件头之后的部分，游程编码由调用方还原
+fn decode_stream_exact<R: BufRead, W: Write>(header: &ContainerHeader, reader: &mut R, mut writer: W) -> Result<DecodeOutcome, HuffmanError> {
+    if header.is_blocks() {
+        return decode_blocks(header, reader, writer);
+    }
+    if header.is_footer() {
+        return decode_footer(header, reader, writer);
+    }
+    let decode_map = header.decode_config(None)?;
+    let mut crc = Crc32::new();
+    if header.is_stored() {
+        let len = decode_map.capacity as u64;
+        copy_exact(reader, &mut writer, len, &mut crc, &mut |_| ())?;
+        header.verify(crc.finish())?;
+        return Ok(DecodeOutcome { bytes_consumed: header.size() as u64 + len, symbols: len });
+    }
+    let mut decoder = BitDecoder::new(&decode_map);
+    let mut out = String::with_capacity(CHUNK_SIZE);
+    while !decoder.is_done() {
+        let buf = reader.fill_buf()?;
+        if buf.is_empty() {
+            break;
+        }
+        // 逐字节解码，解出最后一个字符所在的字节即停止，其后的字节不消耗
+        let mut used = 0;
+        for byte in buf {
+            used += 1;
+            for i in 0..8 {
+                if decoder.is_done() {
+                    break;
+                }
+                if let Some(ch) = decoder.step(byte & decode_map.order.mask(i) != 0)? {
+                    out.push(ch);
+                }
+            }
+            if decoder.is_done() {
+                break;
+            }
+        }
+        reader.consume(used);
+        write_decoded(&mut writer, &mut crc, &out, header.is_bytes())?;
+        out.clear();
+    }
+    decoder.finish()?;
+    writer.flush()?;
+    header.verify(crc.finish())?;
+    Ok(DecodeOutcome {
+        bytes_consumed: (header.size() + decoder.position().div_ceil(8)) as u64,
+        symbols: decoder.symbols() as u64,
+    })
+}
+
+/// 配置在文件末尾的压缩文件无法边读边解码，读入其余全部数据后在内存中解压
+fn decode_footer<R: Read, W: Write>(header: &ContainerHeader, mut reader: R, mut writer: W) -> Result<DecodeOutcome, HuffmanError> {
+    let mut bytes = vec![];
+    header.write(&mut bytes)?;
+    reader.read_to_end(&mut bytes)?;
+    let decode_map = ContainerHeader::parse(&bytes)?.0.decode_config(None)?;
+    writer.write_all(&ByteHuffmanCodec::decode_bytes(&bytes)?)?;
+    writer.flush()?;
+    let symbols = decode_map.count.unwrap_or(decode_map.capacity) as u64;
+    Ok(DecodeOutcome { bytes_consumed: bytes.len() as u64, symbols })
+}
+
+/// 逐块读取并解压分块压缩文件，内存中只保留一块
+fn decode_blocks<R: Read, W: Write>(header: &ContainerHeader, mut reader: R, mut writer: W) -> Result<DecodeOutcome, HuffmanError> {
+    let (capacity, index) = read_block_index(header, &mut reader)?;
+    let mut crc = Crc32::new();
+    let mut written = 0u64;
+    for len in &index {
+        let block = read_block(&mut reader, *len)?;
+        crc.update(&block);
+        writer.write_all(&block)?;
+        written += block.len() as u64;
+    }
+    writer.flush()?;
+    if written != capacity {
+        return Err(HuffmanError::MalformedConfig(format!("capacity:{}", capacity)));
+    }
+    header.verify(crc.finish())?;
+    let index_size = 8 * index.len() as u64;
+    Ok(DecodeOutcome {
+        bytes_consumed: header.size() as u64 + index_size + index.iter().sum::<u64>(),
+        symbols: capacity,
+    })
+}
+
+/// 原样复制 `len` 字节数据并计算 CRC32，每复制一块回调报告已复制的字节数，
+/// 数据不足时返回 `UnexpectedEof`
+fn copy_exact<R: Read, W: Write>(
+    reader: &mut R,
+    writer: &mut W,
+    len: u64,
+    crc: &mut Crc32,
+    progress: &mut dyn FnMut(u64),
+) -> Result<(), HuffmanError> {
+    let mut buf = vec![0u8; CHUNK_SIZE];
+    let mut left = len;
+    while left > 0 {
+        let n = read_some(reader, &mut buf[..left.min(CHUNK_SIZE as u64) as usize])?;
+        if n == 0 {
+            return Err(HuffmanError::UnexpectedEof);
+        }
+        crc.update(&buf[..n]);
+        writer.write_all(&buf[..n])?;
+        left -= n as u64;
+        progress(len - left);
+    }
+    writer.flush()?;
+    Ok(())
+}
+
+/// 写出解码结果并计算 CRC32，字节压缩文件的结果先还原成字节
+fn write_decoded<W: Write>(writer: &mut W, crc: &mut Crc32, out: &str, bytes: bool) -> Result<(), HuffmanError> {
+    if bytes {
+        let bytes = symbols_to_bytes(out)?;
+        crc.update(&bytes);
+        writer.write_all(&bytes)?;
+    } else {
+        crc.update(out.as_bytes());
+        writer.write_all(out.as_bytes())?;
+    }
+    Ok(())
+}
+
+/// 按块读取 UTF-8 文本，每块都在字符边界上切分
+fn read_utf8_chunks<R: Read>(
+    reader: &mut R,
+    mut f: impl FnMut(&str) -> Result<(), HuffmanError>,
+) -> Result<(), HuffmanError> {
+    let mut buf = vec![0u8; CHUNK_SIZE];
+    // buf 开头残留的、被截断的多字节字符长度
+    let mut len = 0;
+    loop {
+        let n = read_some(reader, &mut buf[len..])?;
+        if n == 0 {
+            return if len == 0 { Ok(()) } else { Err(HuffmanError::InvalidUtf8) };
+        }
+        len += n;
+        let text = match std::str::from_utf8(&buf[..len]) {
+            Ok(text) => text,
+            // 末尾的字符被截断，留到下一次读取
+            Err(e) if e.error_len().is_none() => std::str::from_utf8(&buf[..e.valid_up_to()]).unwrap(),
+            Err(_) => return Err(HuffmanError::InvalidUtf8),
+        };
+        let valid = text.len();
+        f(text)?;
+        buf.copy_within(valid..len, 0);
+        len -= valid;
+    }
+}
@@ -0,0 +1,158 @@
+//! 多个文件共用的编码表
+//!
+//! 压缩大量相似的文件（如日志）时，可以先由代表性的样本得到一张编码表，
+//! 再用它压缩每个文件。压缩文件的配置中只记录编码表的编号（编码表文本的 CRC32），
+//! 不再各自附带整张编码表；解压时需要提供同一张编码表。
+//!
+//! 也可以像 zlib 的预设字典那样，以一段已知的公共文本（如日志格式）作为字典预先提供权重，
+//! 见 [`HuffmanCodec::encode_with_dictionary`]：常见的结构性字符即使在很小的文件中也能得到短编码。
+
+use alloc::{borrow::ToOwned, format, string::String};
+
+#[cfg(feature = "std")]
+use crate::container::ContainerHeader;
+use crate::crc32::crc32;
+use crate::huffman::{CharWeightMap, DecodeConfig, DecodeTree, HuffmanBinaryMap, HuffmanError, HuffmanTree};
+#[cfg(feature = "std")]
+use crate::huffman::HuffmanCodec;
+
+/// 可保存并在多个文件之间共用的编码表
+pub struct CodeTable {
+    weights: CharWeightMap,
+    bit_map: HuffmanBinaryMap,
+    decode_map: DecodeConfig,
+    id: u32,
+}
+
+impl CodeTable {
+    /// 由字符权重构建编码表
+    pub fn from_weights(weights: CharWeightMap) -> Result<Self, HuffmanError> {
+        let bit_map = HuffmanBinaryMap::build(&HuffmanTree::build(weights.clone())?)?;
+        let text = Self::text_of(&weights);
+        // 编码表文本不含补位记录，解析时补上，编号仍按原文本计算
+        let decode_map = DecodeConfig::build(&format!("space:0\n{}", text))?;
+        Ok(Self { weights, bit_map, decode_map, id: crc32(text.as_bytes()) })
+    }
+
+    /// 由样本文本统计权重并构建编码表
+    pub fn from_sample(sample: &str) -> Result<Self, HuffmanError> {
+        Self::from_weights(CharWeightMap::build(sample))
+    }
+
+    /// 保存编码表用的文本，格式与配置中的权重表相同
+    pub fn to_text(&self) -> String {
+        Self::text_of(&self.weights)
+    }
+
+    /// 读取 [`to_text`](Self::to_text) 保存的编码表
+    pub fn from_text(source: &str) -> Result<Self, HuffmanError> {
+        let mut weights = CharWeightMap::build("");
+        for line in source.lines().filter(|line| !line.is_empty() && *line != "table:weight") {
+            let malformed = || HuffmanError::MalformedConfig(line.to_owned());
+            let (symbol, weight) = line.split_once(':').ok_or_else(malformed)?;
+            let symbol = DecodeConfig::parse_symbol(symbol).ok_or_else(malformed)?;
+            weights.inner.insert(symbol, weight.parse().map_err(|_| malformed())?);
+        }
+        Self::from_weights(weights)
+    }
+
+    /// 编码表的编号，即其文本的 CRC32，压缩文件凭此找到对应的编码表
+    pub fn id(&self) -> u32 {
+        self.id
+    }
+
+    pub fn bit_map(&self) -> &HuffmanBinaryMap {
+        &self.bit_map
+    }
+
+    /// 解码用的前缀树，可交给 [`HuffmanCodec::decode_with_tree`] 解码由 [`bit_map`](Self::bit_map) 打包的数据
+    pub fn decode_tree(&self) -> &DecodeTree {
+        &self.decode_map.tree
+    }
+
+    pub(crate) fn decode_map(&self) -> &DecodeConfig {
+        &self.decode_map
+    }
+
+    pub(crate) fn weights(&self) -> &CharWeightMap {
+        &self.weights
+    }
+
+    fn text_of(weights: &CharWeightMap) -> String {
+        format!("table:weight\n{}", weights)
+    }
+}
+
+/// 使用共享编码表的编解码器，由 [`HuffmanCodec::with_table`] 创建
+#[cfg(feature = "std")]
+pub struct SharedTableCodec<'a> {
+    table: &'a CodeTable,
+}
+
+#[cfg(feature = "std")]
+impl HuffmanCodec {
+    /// 使用共享编码表压缩 / 解压，不再为每个文件重新统计权重
+    pub fn with_table(table: &CodeTable) -> SharedTableCodec<'_> {
+        SharedTableCodec { table }
+    }
+
+    /// 以 `dictionary` 的字符权重为基础压缩 `source`，得到单个压缩文件内容
+    ///
+    /// 字典中没有的字符按其在 `source` 中的权重补充，配置中只记录字典编号与这些补充的权重；
+    /// 解压时需由 [`decode_with_dictionary`](Self::decode_with_dictionary) 提供同一个字典。
+    /// 压缩后反而不比原文小时改为原样保存
+    pub fn encode_with_dictionary(dictionary: &str, source: &str) -> Result<Vec<u8>, HuffmanError> {
+        let table = CodeTable::from_sample(dictionary)?;
+        let mut extra = CharWeightMap::build(source);
+        extra.inner.retain(|ch, _| !table.weights().inner.contains_key(ch));
+        let mut weights = extra.clone();
+        weights.merge(table.weights())?;
+        let bit_map = HuffmanBinaryMap::build(&HuffmanTree::build(weights)?)?;
+        let (payload, space) = HuffmanCodec::pack(source, &bit_map)?;
+        let entries = format!("id:{:08X}\n{}", table.id(), extra);
+        let config = HuffmanCodec::config_text(space, source.len(), source.chars().count(), "dictionary", &entries);
+        let checksum = crc32(source.as_bytes());
+        let (header, data) = if config.len() + payload.len() < source.len() {
+            (ContainerHeader::new(checksum, config), payload.as_slice())
+        } else {
+            (ContainerHeader::stored(checksum, source.len() as u64), source.as_bytes())
+        };
+        let mut result = Vec::with_capacity(header.size() + data.len());
+        header.write(&mut result)?;
+        result.extend_from_slice(data);
+        Ok(result)
+    }
+
+    /// 解压由 [`encode_with_dictionary`](Self::encode_with_dictionary) 生成的压缩文件内容
+    ///
+    /// 字典与压缩时不同时返回 `TableMismatch`；不使用字典的普通压缩文件同样可以解压
+    pub fn decode_with_dictionary(dictionary: &str, archive: &[u8]) -> Result<String, HuffmanError> {
+        HuffmanCodec::decode_container_with_table(archive, Some(&CodeTable::from_sample(dictionary)?))
+    }
+}
+
+#[cfg(feature = "std")]
+impl<'a> SharedTableCodec<'a> {
+    /// 压缩成单个压缩文件内容，配置中只记录编码表编号
+    ///
+    /// 文本中有编码表里没有的字符时返回 `MissingCode`
+    pub fn encode(&self, source: &str) -> Result<Vec<u8>, HuffmanError> {
+        let (payload, space) = HuffmanCodec::pack(source, self.table.bit_map())?;
+        let config = format!(
+            "space:{}\ncapacity:{}\ncount:{}\ntable:shared\nid:{:08X}\n",
+            space, source.len(), source.chars().count(), self.table.id(),
+        );
+        let header = ContainerHeader::new(crc32(source.as_bytes()), config);
+        let mut result = Vec::with_capacity(header.size() + payload.len());
+        header.write(&mut result)?;
+        result.extend_from_slice(&payload);
+        Ok(result)
+    }
+
+    /// 解压由 [`encode`](Self::encode) 生成的压缩文件内容，编码表编号不符时返回 `TableMismatch`
+    ///
+    /// 不引用共享编码表的普通压缩文件同样可以解压
+    pub fn decode(&self, archive: &[u8]) -> Result<String, HuffmanError> {
+        HuffmanCodec::decode_container_with_table(archive, Some(self.table))
+    }
+}
@@ -0,0 +1,245 @@
+//! 命令行的集成测试：在临时目录中运行编译出的 `rust-huffman`
+
+use std::{
+    fs,
+    io::Write,
+    path::{Path, PathBuf},
+    process::{Command, Output, Stdio},
+};
+
+/// 每个测试独占的临时目录，已存在时先清空
+fn temp_dir(name: &str) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("rust-huffman-cli-{}-{}", name, std::process::id()));
+    let _ = fs::remove_dir_all(&dir);
+    fs::create_dir_all(&dir).unwrap();
+    dir
+}
+
+/// 在 `dir` 中运行命令行，提示信息固定为英文
+fn run(dir: &Path, args: &[&str]) -> Output {
+    command(dir, args).output().unwrap()
+}
+
+/// 与 [`run`] 相同，并把 `input` 写入标准输入
+fn run_with_stdin(dir: &Path, args: &[&str], input: &[u8]) -> Output {
+    let mut child = command(dir, args)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .unwrap();
+    child.stdin.take().unwrap().write_all(input).unwrap();
+    child.wait_with_output().unwrap()
+}
+
+fn command(dir: &Path, args: &[&str]) -> Command {
+    let mut command = Command::new(env!("CARGO_BIN_EXE_rust-huffman"));
+    command.args(args)
+        .current_dir(dir)
+        .env("LANG", "C")
+        .env_remove("LC_ALL")
+        .env_remove("LC_MESSAGES");
+    command
+}
+
+fn stdout(output: &Output) -> String {
+    String::from_utf8_lossy(&output.stdout).into_owned()
+}
+
+fn stderr(output: &Output) -> String {
+    String::from_utf8_lossy(&output.stderr).into_owned()
+}
+
+#[test]
+fn compress_decompress_roundtrip() {
+    let dir = temp_dir("roundtrip");
+    let text = "It was the best of times, it was the worst of times.\n".repeat(40) + "泥嚎 😀\n";
+    fs::write(dir.join("notes.txt"), &text).unwrap();
+
+    // 状态信息写到标准错误，标准输出保持为空
+    let output = run(&dir, &["compress", "notes.txt"]);
+    assert!(output.status.success(), "{}", stderr(&output));
+    assert_eq!(stdout(&output), "");
+    let out = stderr(&output);
+    assert!(out.contains("Compressed!") && out.contains("Saved as: notes.hfm"), "{}", out);
+    assert!(out.contains(&format!("Original size: {} bytes", text.len())), "{}", out);
+    assert!(out.contains("Ratio: "), "{}", out);
+    assert!(run(&dir, &["verify", "notes.txt", "notes.hfm"]).status.success());
+
+    // 不指定输出路径时还原为文件头中记录的原文件名
+    fs::remove_file(dir.join("notes.txt")).unwrap();
+    let output = run(&dir, &["notes.hfm"]);
+    assert!(output.status.success(), "{}", stderr(&output));
+    let out = stderr(&output);
+    assert!(out.contains("Decompressed!") && out.contains("Saved to: notes.txt"), "{}", out);
+    assert_eq!(fs::read_to_string(dir.join("notes.txt")).unwrap(), text);
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn stdin_stdout_roundtrip() {
+    let dir = temp_dir("stdio");
+    let text = b"piped through stdin, piped through stdout\n".repeat(20);
+    let compressed = run_with_stdin(&dir, &["compress", "-"], &text);
+    assert!(compressed.status.success(), "{}", stderr(&compressed));
+    assert!(stderr(&compressed).contains("Compressed!"));
+    let restored = run_with_stdin(&dir, &["-"], &compressed.stdout);
+    assert!(restored.status.success(), "{}", stderr(&restored));
+    assert_eq!(restored.stdout, text);
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn quiet_prints_only_errors() {
+    let dir = temp_dir("quiet");
+    fs::write(dir.join("notes.txt"), "quiet please, quiet please\n").unwrap();
+    for args in [
+        &["compress", "--estimate", "-q", "notes.txt"][..],
+        &["compress", "-q", "notes.txt"][..],
+        &["verify", "--quiet", "notes.txt", "notes.hfm"][..],
+        &["decompress", "-q", "notes.hfm", "-o", "restored.txt", "--rm"][..],
+    ] {
+        let output = run(&dir, args);
+        assert!(output.status.success(), "{:?}: {}", args, stderr(&output));
+        assert_eq!((stdout(&output), stderr(&output)), (String::new(), String::new()), "{:?}", args);
+    }
+    assert_eq!(fs::read_to_string(dir.join("restored.txt")).unwrap(), "quiet please, quiet please\n");
+    assert!(!dir.join("notes.hfm").exists());
+    // 错误仍然输出
+    let output = run(&dir, &["compress", "-q", "missing.txt"]);
+    assert_eq!(output.status.code(), Some(1));
+    assert!(!stderr(&output).is_empty());
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn bad_arguments_print_usage() {
+    let dir = temp_dir("args");
+    for (args, message) in [
+        (&["compress", "--bogus", "x"][..], "unknown option: --bogus"),
+        (&["compress", "x", "-o"][..], "missing output path after -o"),
+        (&["compress"][..], "Usage:"),
+        (&["verify", "a"][..], "Usage:"),
+    ] {
+        let output = run(&dir, args);
+        assert_eq!(output.status.code(), Some(2), "{:?}", args);
+        assert!(stderr(&output).contains(message), "{:?}: {}", args, stderr(&output));
+    }
+    // --help 把用法写到标准输出并正常退出，忽略其余参数
+    for args in [&["--help"][..], &["compress", "-h"][..], &["verify", "a", "--help"][..]] {
+        let output = run(&dir, args);
+        assert!(output.status.success(), "{:?}: {}", args, stderr(&output));
+        assert!(stdout(&output).starts_with("Usage:"), "{:?}", args);
+    }
+    // 输入文件不存在时以状态 1 退出
+    let output = run(&dir, &["compress", "missing.txt"]);
+    assert_eq!(output.status.code(), Some(1));
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn compressed_name_replaces_only_the_last_extension() {
+    let dir = temp_dir("names");
+    fs::create_dir_all(dir.join("dir.d/sub")).unwrap();
+    let cases = [
+        ("report.2024.txt", "report.2024.hfm"),
+        ("a.b.c", "a.b.hfm"),
+        ("noext", "noext.hfm"),
+        (".hidden", ".hidden.hfm"),
+        ("dir.d/file", "dir.d/file.hfm"),
+        ("dir.d/sub/x.log", "dir.d/sub/x.hfm"),
+    ];
+    for (input, expected) in cases {
+        fs::write(dir.join(input), "hello path hello path\n").unwrap();
+        let output = run(&dir, &["compress", input]);
+        assert!(output.status.success(), "{}: {}", input, stderr(&output));
+        assert!(dir.join(expected).is_file(), "{} -> {}", input, expected);
+    }
+    // 上级目录中的文件，目录名中的 `.` 不影响结果
+    let output = run(&dir.join("dir.d/sub"), &["compress", "../file"]);
+    assert!(output.status.success());
+    assert!(dir.join("dir.d/file.hfm").is_file());
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn swapped_decompress_paths_keep_the_archive() {
+    let dir = temp_dir("swapped");
+    fs::write(dir.join("notes.txt"), "swap me swap me\n").unwrap();
+    assert!(run(&dir, &["compress", "notes.txt"]).status.success());
+    let archive = fs::read(dir.join("notes.hfm")).unwrap();
+    // 原文件与压缩文件填反时报错，且不覆盖压缩文件
+    let output = run(&dir, &["decompress", "notes.txt", "-o", "notes.hfm"]);
+    assert!(!output.status.success());
+    assert!(stderr(&output).contains("swapped"));
+    assert_eq!(fs::read(dir.join("notes.hfm")).unwrap(), archive);
+    // 把配置当作压缩文件解压
+    let (_, config) = rust_huffman::compress_bytes(b"swap me");
+    fs::write(dir.join("config.txt"), config).unwrap();
+    let output = run(&dir, &["decompress", "config.txt", "-o", "out.txt"]);
+    assert!(stderr(&output).contains("swapped"));
+    assert!(!dir.join("out.txt").exists());
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn sampled_compress_roundtrip() {
+    let dir = temp_dir("sampled");
+    let mut text = "sampled prefix ".repeat(100);
+    text.push_str("then a new tail: ÆØÅ 😀\n");
+    fs::write(dir.join("big.txt"), &text).unwrap();
+    let output = run(&dir, &["compress", "--sample", "64", "big.txt"]);
+    assert!(output.status.success(), "{}", stderr(&output));
+    let output = run(&dir, &["decompress", "big.hfm", "-o", "restored.txt"]);
+    assert!(output.status.success(), "{}", stderr(&output));
+    assert_eq!(fs::read_to_string(dir.join("restored.txt")).unwrap(), text);
+    assert!(!run(&dir, &["compress", "--sample", "0", "big.txt"]).status.success());
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn decompress_legacy_two_file_layout() {
+    let dir = temp_dir("legacy");
+    // 旧版压缩文件：不带文件头的压缩数据，配置为十进制码点与编码，`aab` 编码为 001 后补 5 位 0
+    fs::write(dir.join("old.hfm"), [0b0010_0000]).unwrap();
+    fs::write(dir.join("old.hfm.config"), "space:5\ncapacity:3\n97:0\n98:1\n").unwrap();
+    let output = run(&dir, &["decompress", "old.hfm"]);
+    assert!(output.status.success(), "{}", stderr(&output));
+    assert_eq!(fs::read_to_string(dir.join("old")).unwrap(), "aab");
+
+    // 自包含的压缩文件即使旁边有同名的 `.config` 也不读取它
+    fs::write(dir.join("new.txt"), "self contained self contained\n").unwrap();
+    assert!(run(&dir, &["compress", "new.txt"]).status.success());
+    fs::write(dir.join("new.hfm.config"), "space:0\ncapacity:0\n").unwrap();
+    let output = run(&dir, &["decompress", "new.hfm", "-o", "new.out"]);
+    assert!(output.status.success(), "{}", stderr(&output));
+    assert_eq!(fs::read_to_string(dir.join("new.out")).unwrap(), "self contained self contained\n");
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn shared_table_compress_decompress() {
+    let dir = temp_dir("table");
+    fs::write(dir.join("corpus.log"), "GET /index.html 200\nPOST /login 302\n".repeat(50)).unwrap();
+    let output = run(&dir, &["build-table", "corpus.log", "-o", "table.json"]);
+    assert!(output.status.success(), "{}", stderr(&output));
+    assert!(fs::read_to_string(dir.join("table.json")).unwrap().starts_with("{\"weights\":{"));
+
+    let text = "GET /login 200\nPOST /index.html 302\n";
+    fs::write(dir.join("today.log"), text).unwrap();
+    // `--rm` 校验时同样使用共享编码表
+    let output = run(&dir, &["compress", "--table", "table.json", "--rm", "today.log"]);
+    assert!(output.status.success(), "{}", stderr(&output));
+    assert!(!dir.join("today.log").exists());
+    let output = run(&dir, &["decompress", "--table", "table.json", "today.hfm", "-o", "restored.log"]);
+    assert!(output.status.success(), "{}", stderr(&output));
+    assert_eq!(fs::read_to_string(dir.join("restored.log")).unwrap(), text);
+
+    // 没有提供编码表，或文本中有编码表里没有的字符时报错
+    assert!(!run(&dir, &["decompress", "today.hfm", "-o", "nope.log"]).status.success());
+    fs::write(dir.join("other.log"), "DELETE /\n").unwrap();
+    let output = run(&dir, &["compress", "--table", "table.json", "other.log"]);
+    assert!(!output.status.success());
+    assert!(!dir.join("other.hfm").exists());
+    fs::remove_dir_all(&dir).unwrap();
+}
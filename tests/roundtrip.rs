@@ -0,0 +1,1177 @@
+//! 随机输入的往返测试：`decode(encode(x)) == x`
+
+use std::{
+    cell::RefCell,
+    convert::{TryFrom, TryInto},
+    fs,
+    io::Cursor,
+    rc::Rc,
+};
+
+use rust_huffman::{
+    padding_bits, AdaptiveHuffmanCodec, BitOrder, BitReader, BitWriter, ByteHuffmanCodec, CharWeightMap, CodeTable,
+    ContainerMeta, DecodeConfig, DecodeTree, HfmArchive, HuffmanBinaryMap, HuffmanCodec, HuffmanError, HuffmanTree,
+};
+
+/// 简单的 xorshift 伪随机数生成器，固定种子保证测试可复现
+struct Rng(u64);
+
+impl Rng {
+    fn next(&mut self) -> u64 {
+        self.0 ^= self.0 << 13;
+        self.0 ^= self.0 >> 7;
+        self.0 ^= self.0 << 17;
+        self.0
+    }
+
+    fn below(&mut self, n: u64) -> u64 {
+        self.next() % n
+    }
+
+    /// 任意合法的 Unicode 字符，包括换行、`:` 与辅助平面字符
+    fn any_char(&mut self) -> char {
+        loop {
+            if let Some(ch) = char::from_u32(self.below(0x11_0000) as u32) {
+                return ch;
+            }
+        }
+    }
+
+    /// 从给定字母表中随机取字符组成的文本
+    fn text_from(&mut self, alphabet: &[char], len: usize) -> String {
+        (0..len).map(|_| alphabet[self.below(alphabet.len() as u64) as usize]).collect()
+    }
+}
+
+/// 各种长度与字符分布的输入
+fn samples() -> Vec<String> {
+    let mut rng = Rng(0x9E37_79B9_7F4A_7C15);
+    let mut samples = vec![
+        String::new(),
+        "a".to_owned(),
+        "aaaaaaaaaaaaaaaa".to_owned(),
+        "ab".to_owned(),
+        "\n".repeat(1000),
+        ":\n\t😀".repeat(33),
+    ];
+    let ascii = (0x20u8..0x7F).map(char::from).chain(['\n', '\t']).collect::<Vec<_>>();
+    let skewed = ['e', 'e', 'e', 'e', 'e', 'e', 'e', 't', 't', 't', 'a', 'o', ' ', '\n'];
+    for len in [1, 2, 3, 7, 8, 9, 100, 1000, 10_000] {
+        samples.push(rng.text_from(&ascii, len));
+        samples.push(rng.text_from(&skewed, len));
+        samples.push((0..len).map(|_| rng.any_char()).collect());
+        let ch = rng.any_char();
+        samples.push(std::iter::repeat_n(ch, len).collect());
+    }
+    for _ in 0..50 {
+        let len = rng.below(300) as usize;
+        let alphabet = (0..1 + rng.below(20)).map(|_| rng.any_char()).collect::<Vec<_>>();
+        samples.push(rng.text_from(&alphabet, len));
+    }
+    samples
+}
+
+#[test]
+fn encode_decode_roundtrip() {
+    for source in samples() {
+        let (payload, config) = HuffmanCodec::encode(&source).unwrap();
+        let decode_map = DecodeConfig::build(&config).unwrap();
+        assert_eq!(HuffmanCodec::decode(&payload, &decode_map).unwrap(), source);
+    }
+}
+
+#[test]
+fn decode_config_from_str_literal() {
+    let decode_map = DecodeConfig::build("space:5\ncapacity:3\ncount:3\ntable:weight\nU+0061:2\nU+0062:1\n").unwrap();
+    assert_eq!(HuffmanCodec::decode(&[0b1100_0000], &decode_map).unwrap(), "aab");
+    assert_eq!(decode_map.get("1"), Some(&'a'));
+    assert_eq!(decode_map.get("0"), Some(&'b'));
+    assert_eq!(decode_map.get("01"), None);
+}
+
+#[test]
+fn crlf_config_roundtrip() {
+    for source in samples() {
+        let (payload, config) = HuffmanCodec::encode(&source).unwrap();
+        let decode_map = DecodeConfig::build(&config.replace('\n', "\r\n")).unwrap();
+        assert_eq!(HuffmanCodec::decode(&payload, &decode_map).unwrap(), source);
+    }
+    let decode_map = DecodeConfig::build("space:5\r\ncapacity:3\r\ncount:3\r\ntable:code\r\nU+0061:1\r\nU+0062:0").unwrap();
+    assert_eq!(decode_map.get("1"), Some(&'a'));
+    assert_eq!(decode_map.get("0"), Some(&'b'));
+}
+
+#[test]
+fn in_memory_bytes_roundtrip() {
+    let mut rng = Rng(0xB17E);
+    let mut inputs = samples().into_iter().map(String::into_bytes).collect::<Vec<_>>();
+    inputs.push((0..5000).map(|_| rng.below(256) as u8).collect());
+    inputs.push(vec![]);
+    for source in inputs {
+        let (payload, config) = rust_huffman::compress_bytes(&source);
+        assert_eq!(rust_huffman::decompress_bytes(&payload, &config).unwrap(), source);
+        assert_eq!(rust_huffman::decompress(&rust_huffman::compress(&source)).unwrap(), source);
+    }
+    assert!(matches!(rust_huffman::decompress_bytes(&[], &[0xFF]), Err(HuffmanError::MalformedConfig(_))));
+    // 压缩数据与配置颠倒
+    let (payload, config) = rust_huffman::compress_bytes(b"swapped swapped");
+    assert!(HuffmanCodec::is_config(&config) && !HuffmanCodec::is_config(&payload));
+    assert!(matches!(rust_huffman::decompress_bytes(&config, &payload), Err(HuffmanError::SwappedInputs)));
+}
+
+/// 10 MB 只有一种字节的文件，每个字节编码为 1 位
+#[test]
+fn single_byte_10mb_roundtrip() {
+    let source = vec![b'x'; 10 * 1024 * 1024];
+    let archive = rust_huffman::compress(&source);
+    assert!(archive.len() < source.len() / 8 + 100, "{} bytes", archive.len());
+    assert_eq!(rust_huffman::decompress(&archive).unwrap(), source);
+}
+
+#[test]
+fn container_roundtrip() {
+    for source in samples() {
+        let archive = HuffmanCodec::encode_to_container(&source).unwrap();
+        assert_eq!(HuffmanCodec::decode_from_container(&archive).unwrap(), source);
+    }
+}
+
+#[test]
+fn stream_roundtrip() {
+    for source in samples() {
+        let mut archive = vec![];
+        HuffmanCodec::encode_reader(Cursor::new(source.as_bytes()), &mut archive).unwrap();
+        let mut output = vec![];
+        HuffmanCodec::decode_reader(&archive[..], &mut output).unwrap();
+        assert_eq!(output, source.as_bytes());
+        // 流式与一次性接口生成的文件可以互相解码
+        assert_eq!(archive, HuffmanCodec::encode_to_container(&source).unwrap());
+
+        // 同时返回的权重即第一遍统计的结果，原样保存时也一样
+        let mut again = vec![];
+        let (report, weights) = HuffmanCodec::encode_reader_with_weights(
+            Cursor::new(source.as_bytes()), &mut again, ContainerMeta::default(), usize::MAX, |_, _| (),
+        ).unwrap();
+        assert_eq!(again, archive);
+        assert_eq!(report.compressed, archive.len());
+        assert_eq!(weights.inner, CharWeightMap::build(&source).inner);
+    }
+}
+
+#[test]
+fn canonical_roundtrip() {
+    for source in samples() {
+        let (payload, config) = HuffmanCodec::encode_canonical(&source).unwrap();
+        let decode_map = DecodeConfig::build(&config).unwrap();
+        assert_eq!(HuffmanCodec::decode(&payload, &decode_map).unwrap(), source);
+    }
+}
+
+#[test]
+fn limited_roundtrip() {
+    for source in samples() {
+        let (payload, config) = HuffmanCodec::encode_limited(&source, 16).unwrap();
+        let decode_map = DecodeConfig::build(&config).unwrap();
+        assert_eq!(HuffmanCodec::decode(&payload, &decode_map).unwrap(), source);
+    }
+}
+
+/// 权重为斐波那契数列的 20 个字符，不限制时最长的编码有 19 位，需要重新调整编码长度
+#[test]
+fn limited_lengths_rebalance_fibonacci_weights() {
+    let (mut a, mut b) = (1usize, 1usize);
+    let mut source = String::new();
+    for ch in ('a'..='t').rev() {
+        source.extend(std::iter::repeat_n(ch, a));
+        (a, b) = (b, a + b);
+    }
+    let bit_map = HuffmanBinaryMap::build(&HuffmanTree::build(CharWeightMap::build(&source)).unwrap()).unwrap();
+    assert_eq!(bit_map.code_lengths().values().max(), Some(&19));
+    for max_len in [5, 8, 12] {
+        let lengths = bit_map.limited_lengths(max_len).unwrap();
+        assert_eq!(lengths.len(), 20);
+        assert!(lengths.values().all(|len| *len <= max_len), "{:?}", lengths);
+        // 调整后的长度仍能构成无歧义的范式编码
+        let limited = HuffmanBinaryMap::from_lengths(&lengths);
+        let (payload, space) = HuffmanCodec::pack(&source, &limited).unwrap();
+        let tree = DecodeTree::from_bit_map(&limited).unwrap();
+        assert_eq!(HuffmanCodec::decode_with_tree(&payload, &tree, space).unwrap(), source);
+
+        let (payload, config) = HuffmanCodec::encode_limited(&source, max_len).unwrap();
+        assert_eq!(HuffmanCodec::decode(&payload, &DecodeConfig::build(&config).unwrap()).unwrap(), source);
+    }
+    // 4 位最多区分 16 个字符
+    assert!(matches!(bit_map.limited_lengths(4), Err(HuffmanError::MaxLengthTooSmall(4))));
+    assert!(matches!(HuffmanCodec::encode_limited(&source, 4), Err(HuffmanError::MaxLengthTooSmall(4))));
+}
+
+/// 只含换行或空白的文件：换行符既是配置的行分隔符又是被编码的字符，二者不能混淆
+#[test]
+fn whitespace_only_roundtrip() {
+    for source in ["\n".repeat(1000), "\r\n".repeat(500), " \t\r\n".repeat(250)] {
+        let (payload, config) = HuffmanCodec::encode(&source).unwrap();
+        // 字符以码点写入，配置中不会因为编码换行符而多出空行
+        assert!(config.lines().all(|line| !line.is_empty()));
+        let decode_map = DecodeConfig::build(&config).unwrap();
+        assert_eq!(HuffmanCodec::decode(&payload, &decode_map).unwrap(), source);
+
+        // 直接记录编码的配置同样不受影响
+        let decode_map = DecodeConfig::from_json(&decode_map.to_json()).unwrap();
+        assert_eq!(HuffmanCodec::decode(&payload, &decode_map).unwrap(), source);
+
+        let archive = HuffmanCodec::encode_to_container(&source).unwrap();
+        assert_eq!(HuffmanCodec::decode_from_container(&archive).unwrap(), source);
+    }
+}
+
+/// JSON 配置中 `codes` 的键只能是字符，不能借此写入 `count`、`table` 等其他字段
+#[test]
+fn json_code_keys_must_be_symbols() {
+    let (payload, config) = HuffmanCodec::encode("aab").unwrap();
+    let json = DecodeConfig::build(&config).unwrap().to_json();
+    assert_eq!(HuffmanCodec::decode(&payload, &DecodeConfig::from_json(&json).unwrap()).unwrap(), "aab");
+    for key in ["count", "space", "table", "id", "capacity", "ab", ""] {
+        let injected = json.replacen("\"codes\":{", &format!("\"codes\":{{\"{}\":\"1\",", key), 1);
+        assert!(matches!(DecodeConfig::from_json(&injected), Err(HuffmanError::MalformedConfig(_))), "{}", key);
+    }
+    // 十进制码点与十六进制码点都按字符写入
+    let decimal = json.replace("\"U+0061\"", "\"97\"");
+    assert_eq!(HuffmanCodec::decode(&payload, &DecodeConfig::from_json(&decimal).unwrap()).unwrap(), "aab");
+}
+
+/// 辅助平面的 emoji 在配置中写成完整的码点，不会被截断成单个代码单元
+#[test]
+fn astral_symbols_roundtrip() {
+    let source = "😀👍🏽 𝄞 \u{10FFFF}😀😀";
+    let (payload, config) = HuffmanCodec::encode(source).unwrap();
+    assert!(config.contains("U+1F600:"));
+    assert!(config.contains("U+10FFFF:"));
+    let decode_map = DecodeConfig::build(&config).unwrap();
+    assert_eq!(HuffmanCodec::decode(&payload, &decode_map).unwrap(), source);
+
+    // 字段不是恰好一个码点时报错，而不是只取其中一部分
+    for record in ["😀:0", "U+1F600x:0", "U++61:0", "U+D800:0", "U+110000:0", "+97:0", "U+:0"] {
+        let config = format!("space:0\ncapacity:1\ncount:1\ntable:code\n{}\n", record);
+        assert!(
+            matches!(DecodeConfig::build(&config), Err(HuffmanError::MalformedConfig(_))),
+            "{}", record,
+        );
+    }
+}
+
+/// 单一字符重复 0 ~ 16 次：编码为 1 位，补位数量与字节数随长度变化，字符数量保证不会把补位解成字符
+#[test]
+fn repeated_symbol_matrix() {
+    for len in 0..=16 {
+        let source = "x".repeat(len);
+        let (payload, config) = HuffmanCodec::encode(&source).unwrap();
+        assert_eq!(payload.len(), len.div_ceil(8), "len {}", len);
+        assert!(config.contains(&format!("space:{}\n", (8 - len % 8) % 8)), "len {}", len);
+        assert!(config.contains(&format!("count:{}\n", len)), "len {}", len);
+        let decode_map = DecodeConfig::build(&config).unwrap();
+        assert_eq!(HuffmanCodec::decode(&payload, &decode_map).unwrap(), source, "len {}", len);
+
+        let archive = HuffmanCodec::encode_to_container(&source).unwrap();
+        assert_eq!(HuffmanCodec::decode_from_container(&archive).unwrap(), source, "len {}", len);
+
+        let mut output = vec![];
+        HuffmanCodec::decode_reader(&archive[..], &mut output).unwrap();
+        assert_eq!(output, source.as_bytes(), "len {}", len);
+
+        let archive = ByteHuffmanCodec::encode_bytes(source.as_bytes()).unwrap();
+        assert_eq!(ByteHuffmanCodec::decode_bytes(&archive).unwrap(), source.as_bytes(), "len {}", len);
+
+        let encoded = AdaptiveHuffmanCodec::encode(&source);
+        assert_eq!(AdaptiveHuffmanCodec::decode(&encoded).unwrap(), source, "len {}", len);
+    }
+}
+
+/// 去掉 `space` 一行的配置报 `MissingPadding`，而不是默认补位为 0 把补位解成多余的字符
+#[test]
+fn missing_space_is_rejected() {
+    let (_, config) = HuffmanCodec::encode("abracadabra").unwrap();
+    let stripped = config.lines()
+        .filter(|line| !line.starts_with("space:"))
+        .map(|line| format!("{}\n", line))
+        .collect::<String>();
+    assert_ne!(stripped, config);
+    assert!(matches!(DecodeConfig::build(&stripped), Err(HuffmanError::MissingPadding)));
+}
+
+/// 一个编码是另一个编码的前缀时无法唯一解码，配置被拒绝
+#[test]
+fn prefix_codes_in_config_are_rejected() {
+    let config = "space:0\ncapacity:2\ncount:2\ntable:code\nU+0061:01\nU+0062:010\n";
+    assert!(matches!(DecodeConfig::build(config), Err(HuffmanError::AmbiguousCodes)));
+}
+
+/// 接近上限的权重相加会溢出，建树时返回 `WeightOverflow`，由权重重建编码表的配置同样如此
+#[test]
+fn near_max_weights_overflow() {
+    let weights = CharWeightMap::from_map(vec![('a', u64::MAX), ('b', 1)].into_iter().collect());
+    assert!(matches!(HuffmanTree::build(weights), Err(HuffmanError::WeightOverflow)));
+    let config = "space:0\ncapacity:2\ncount:2\ntable:weight\nU+0061:18446744073709551615\nU+0062:1\n";
+    assert!(matches!(DecodeConfig::build(config), Err(HuffmanError::WeightOverflow)));
+}
+
+/// 补位不可能达到 8 位；记录了字符数量却没有任何数据时报 `UnexpectedEof`，而不是解出空字符串
+#[test]
+fn invalid_padding_and_empty_payload_are_rejected() {
+    let (payload, config) = HuffmanCodec::encode("abracadabra").unwrap();
+    let space = config.lines().find(|line| line.starts_with("space:")).unwrap();
+    for bad in ["space:8", "space:9"] {
+        let config = config.replacen(space, bad, 1);
+        assert!(matches!(DecodeConfig::build(&config), Err(HuffmanError::InvalidPadding)), "{}", bad);
+    }
+    let decode_map = DecodeConfig::build(&config).unwrap();
+    assert!(matches!(decode_map.count, Some(count) if count > 0));
+    assert_eq!(HuffmanCodec::decode(&payload, &decode_map).unwrap(), "abracadabra");
+    assert!(matches!(HuffmanCodec::decode(&[], &decode_map), Err(HuffmanError::UnexpectedEof)));
+}
+
+#[test]
+fn shared_table_roundtrip() {
+    let table = CodeTable::from_sample("hello abc 你好\n").unwrap();
+    // JSON 与文本格式保存后读回的编码表编号相同
+    let from_json = CodeTable::from_json(&table.to_json()).unwrap();
+    assert_eq!(from_json.id(), table.id());
+    assert!(CodeTable::from_json(r#"{"weights":{"U+0061":1},"codes":{}}"#).is_err());
+    let table = CodeTable::from_text(&table.to_text()).unwrap();
+    let codec = HuffmanCodec::with_table(&table);
+    for text in ["hello", "abc 你好\n", ""] {
+        let archive = codec.encode(text).unwrap();
+        assert_eq!(codec.decode(&archive).unwrap(), text);
+    }
+}
+
+/// 根结点的权重即符号数量，每个内部结点的权重为左右子结点之和
+#[test]
+fn tree_weight_matches_symbol_count() {
+    for source in samples() {
+        let tree = HuffmanTree::build(CharWeightMap::build(&source)).unwrap();
+        let root = tree.as_ref().borrow();
+        assert_eq!(root.total_weight(), source.chars().count() as u64);
+        assert_eq!(root.weight(), root.total_weight());
+        for (_, node) in HuffmanTree::dfs(&tree) {
+            let node = node.as_ref().borrow();
+            assert_eq!(node.total_weight(), root.weight());
+            if let (Some(left), Some(right)) = (&node.left, &node.right) {
+                assert_eq!(node.weight(), left.as_ref().borrow().weight() + right.as_ref().borrow().weight());
+            }
+        }
+    }
+}
+
+/// 随机字节数据经 `compress` / `decompress` 往返，包括不可压缩而原样保存的数据
+#[test]
+fn compress_decompress_roundtrip() {
+    let mut rng = Rng(0xD1B5_4A32_D192_ED03);
+    for len in [0, 1, 2, 255, 256, 1000, 65_536] {
+        let random = (0..len).map(|_| rng.next() as u8).collect::<Vec<_>>();
+        let skewed = (0..len).map(|_| (rng.below(4) * rng.below(4)) as u8).collect::<Vec<_>>();
+        for data in [random, skewed] {
+            assert_eq!(rust_huffman::decompress(&rust_huffman::compress(&data)).unwrap(), data, "len {}", len);
+        }
+    }
+}
+
+/// 游程很多的数据先做游程编码能压得更小，各种解码方式都能还原；随机数据不用游程编码
+#[test]
+fn rle_then_huffman_roundtrip() {
+    let mut rng = Rng(0x2545_F491_4F6C_DD1D);
+    // 游程长度覆盖 1、2、拆段的边界 257 与更长的游程
+    let mut runs = vec![];
+    for len in [1, 2, 3, 256, 257, 258, 1000] {
+        runs.extend(vec![rng.next() as u8; len]);
+    }
+    for _ in 0..200 {
+        let len = rng.below(40) as usize + 1;
+        runs.extend(vec![rng.below(8) as u8; len]);
+    }
+    let plain = ByteHuffmanCodec::encode_bytes(&runs).unwrap();
+    let archive = ByteHuffmanCodec::encode_rle_then_huffman(&runs).unwrap();
+    assert!(archive.len() < plain.len() / 2, "{} vs {}", archive.len(), plain.len());
+    assert_eq!(rust_huffman::decompress(&archive).unwrap(), runs);
+    let mut output = vec![];
+    HuffmanCodec::decode_reader(&archive[..], &mut output).unwrap();
+    assert_eq!(output, runs);
+    let mut reader = Cursor::new([&archive[..], b"tail"].concat());
+    let mut output = vec![];
+    HuffmanCodec::decode_exact(&mut reader, &mut output).unwrap();
+    assert_eq!(output, runs);
+    assert_eq!(reader.position() as usize, archive.len());
+
+    // 游程编码没有帮助时与 `encode_bytes` 的结果相同
+    for len in [0, 1, 1000, 65_536] {
+        let random = (0..len).map(|_| rng.next() as u8).collect::<Vec<_>>();
+        let archive = ByteHuffmanCodec::encode_rle_then_huffman(&random).unwrap();
+        assert_eq!(archive, ByteHuffmanCodec::encode_bytes(&random).unwrap(), "len {}", len);
+    }
+}
+
+/// 分块压缩的输出与线程数无关，分块与不分块的压缩文件都能由并行解码器和流式解码器还原
+#[test]
+fn block_roundtrip() {
+    for source in samples() {
+        let data = source.as_bytes();
+        let single = ByteHuffmanCodec::encode_blocks(data, 64, 1).unwrap();
+        assert_eq!(ByteHuffmanCodec::encode_blocks(data, 64, 4).unwrap(), single);
+        let plain = ByteHuffmanCodec::encode_bytes(data).unwrap();
+        for archive in [&single, &plain] {
+            assert_eq!(ByteHuffmanCodec::decode_blocks(archive, 3).unwrap(), data);
+            let mut output = vec![];
+            HuffmanCodec::decode_reader(&archive[..], &mut output).unwrap();
+            assert_eq!(output, data);
+        }
+        assert_eq!(HuffmanCodec::decode_from_container(&single).unwrap(), source);
+    }
+}
+
+/// 块本身不能再是分块压缩文件，否则层层嵌套的输入会在解压时耗尽栈
+#[test]
+fn nested_block_archive_is_rejected() {
+    let data = "nested blocks 日志\n".repeat(20).into_bytes();
+    let inner = ByteHuffmanCodec::encode_blocks(&data, 64, 1).unwrap();
+    // 只有一块的外层压缩文件，其原文长度与 CRC32 与内层相同，把唯一的一块换成内层压缩文件
+    let outer = ByteHuffmanCodec::encode_blocks(&data, data.len(), 1).unwrap();
+    let header_size = 14 + u32::from_le_bytes(outer[10..14].try_into().unwrap()) as usize;
+    let nested = [&outer[..header_size], &(inner.len() as u64).to_le_bytes(), &inner].concat();
+
+    assert!(matches!(ByteHuffmanCodec::decode_blocks(&nested, 2), Err(HuffmanError::MalformedConfig(_))));
+    assert!(HuffmanCodec::decode_from_container(&nested).is_err());
+    assert!(HuffmanCodec::decode_reader(&nested[..], &mut vec![]).is_err());
+    assert!(ByteHuffmanCodec::decode_bytes(&nested).is_err());
+}
+
+/// 每次合并权重最小的两个结点，权重相同时先取先创建的结点，得到的编码固定不变
+#[test]
+fn smallest_pair_is_merged_first() {
+    let mut weights = CharWeightMap::build("");
+    weights.inner.extend([('a', 1), ('b', 1), ('c', 2), ('d', 4)]);
+    let bit_map = HuffmanBinaryMap::build(&HuffmanTree::build(weights).unwrap()).unwrap();
+    let code = |ch| bit_map.get(&ch).unwrap().iter().map(|b| if *b { '1' } else { '0' }).collect::<String>();
+    assert_eq!(code('d'), "0");
+    assert_eq!(code('c'), "10");
+    assert_eq!(code('a'), "110");
+    assert_eq!(code('b'), "111");
+}
+
+/// 手动连接出环或共用结点的树返回 `MalformedTree`，而不是无限递归导致栈溢出
+#[test]
+fn malformed_tree_is_rejected() {
+    let leaf = |ch| Rc::new(RefCell::new(HuffmanTree { value: Some(ch), ..HuffmanTree::new() }));
+    let root = Rc::new(RefCell::new(HuffmanTree::new()));
+    root.borrow_mut().left = Some(leaf('a'));
+    root.borrow_mut().right = Some(root.clone());
+    assert!(matches!(HuffmanBinaryMap::build(&root), Err(HuffmanError::MalformedTree)));
+    // 断开环，避免引用计数泄漏
+    root.borrow_mut().right = None;
+
+    let shared = leaf('b');
+    root.borrow_mut().left = Some(shared.clone());
+    root.borrow_mut().right = Some(shared);
+    assert!(matches!(HuffmanBinaryMap::build(&root), Err(HuffmanError::MalformedTree)));
+}
+
+/// 替换压缩文件的配置，重新写出文件头
+fn with_config(archive: &[u8], edit: impl Fn(&str) -> String) -> Vec<u8> {
+    let len = u32::from_le_bytes(archive[10..14].try_into().unwrap()) as usize;
+    let config = edit(std::str::from_utf8(&archive[14..14 + len]).unwrap());
+    let mut result = archive[..10].to_vec();
+    result.extend_from_slice(&(config.len() as u32).to_le_bytes());
+    result.extend_from_slice(config.as_bytes());
+    result.extend_from_slice(&archive[14 + len..]);
+    result
+}
+
+#[test]
+fn encode_detailed_matches_encode() {
+    for source in samples() {
+        let (payload, config) = HuffmanCodec::encode(&source).unwrap();
+        let (detailed, table, report) = HuffmanCodec::encode_detailed(&source).unwrap();
+        assert_eq!(detailed, payload);
+        assert_eq!(report.original, source.len());
+        assert_eq!(report.compressed, payload.len() + config.len());
+        assert_eq!(report.config_size, config.len());
+        let decode_map = DecodeConfig::build(&config).unwrap();
+        for (ch, code) in table.bit_map().iter() {
+            let code = code.iter().map(|b| if *b { '1' } else { '0' }).collect::<String>();
+            assert_eq!(decode_map.get(&code), Some(ch));
+        }
+        // 有效位数即每个字符编码长度之和，补上补位恰好凑满压缩数据的字节数
+        let bits = source.chars().map(|ch| table.bit_map().get(&ch).unwrap().len() as u64).sum::<u64>();
+        assert_eq!(report.payload_bits, bits);
+        assert_eq!(bits + u64::from(padding_bits(bits as usize)), payload.len() as u64 * 8);
+        let (_, container_report) = HuffmanCodec::encode_with_report(&source).unwrap();
+        let stored = container_report.compressed == container_report.config_size + source.len();
+        assert_eq!(container_report.payload_bits, if stored { source.len() as u64 * 8 } else { bits });
+    }
+}
+
+#[test]
+fn footer_config_roundtrip() {
+    let mut sources = samples();
+    // 原样保存的压缩文件
+    sources.push("x".to_owned());
+    for source in sources {
+        let header = HuffmanCodec::encode_to_container(&source).unwrap();
+        let footer = HuffmanCodec::encode_to_container_with_footer(&source).unwrap();
+        assert_eq!(footer.len(), header.len() + 8);
+        for archive in [&header, &footer] {
+            assert_eq!(HuffmanCodec::decode_from_container(archive).unwrap(), source);
+            assert_eq!(ByteHuffmanCodec::decode_bytes(archive).unwrap(), source.as_bytes());
+            let mut output = vec![];
+            let outcome = HuffmanCodec::decode_reader(&archive[..], &mut output).unwrap();
+            assert_eq!(output, source.as_bytes());
+            assert_eq!(outcome.bytes_consumed, archive.len() as u64);
+        }
+        // 末尾的偏移指向文件之外，或压缩数据之后多出内容
+        let mut bad_offset = footer.clone();
+        let end = bad_offset.len() - 8;
+        bad_offset[end..].copy_from_slice(&u64::MAX.to_le_bytes());
+        assert!(matches!(HuffmanCodec::decode_from_container(&bad_offset), Err(HuffmanError::MalformedConfig(_))));
+        let mut trailing = footer.clone();
+        trailing.push(0);
+        assert!(HuffmanCodec::decode_from_container(&trailing).is_err());
+    }
+}
+
+#[test]
+fn container_meta_roundtrip() {
+    let source = "hello meta ".repeat(20);
+    let plain = HuffmanCodec::encode_to_container(&source).unwrap();
+    let metas = [
+        ContainerMeta::default(),
+        ContainerMeta { filename: Some("日志.txt".to_owned()), mtime: Some(1_700_000_000) },
+        ContainerMeta { filename: Some(String::new()), mtime: None },
+        ContainerMeta { filename: None, mtime: Some(0) },
+    ];
+    for meta in metas {
+        let archive = HuffmanCodec::encode_to_container_with_meta(&source, meta.clone()).unwrap();
+        if meta.is_empty() {
+            assert_eq!(archive, plain);
+        }
+        assert_eq!(HuffmanCodec::decode_with_meta(&archive).unwrap(), (source.clone(), meta.clone()));
+        assert_eq!(HuffmanCodec::read_meta(&archive[..]).unwrap(), meta);
+        assert_eq!(ByteHuffmanCodec::decode_bytes(&archive).unwrap(), source.as_bytes());
+        let mut output = vec![];
+        let outcome = HuffmanCodec::decode_reader(&archive[..], &mut output).unwrap();
+        assert_eq!((output, outcome.bytes_consumed), (source.clone().into_bytes(), archive.len() as u64));
+
+        let mut streamed = vec![];
+        HuffmanCodec::encode_reader_with_meta(Cursor::new(&source), &mut streamed, meta.clone(), usize::MAX, |_, _| ())
+            .unwrap();
+        assert_eq!(HuffmanCodec::decode_with_meta(&streamed).unwrap(), (source.clone(), meta));
+    }
+    // 原样保存的压缩文件同样记录
+    let meta = ContainerMeta { filename: Some("x".to_owned()), mtime: Some(42) };
+    let stored = HuffmanCodec::encode_to_container_with_meta("x", meta.clone()).unwrap();
+    assert_eq!(HuffmanCodec::decode_with_meta(&stored).unwrap(), ("x".to_owned(), meta));
+}
+
+/// 按记录的字符数量解码：压缩数据之后多出的字节不会被解成字符，缺少字符数量的压缩文件被拒绝
+#[test]
+fn overlong_payload_stops_at_count() {
+    for source in ["abracadabra ".repeat(20), "x".repeat(100)] {
+        let source = source.as_str();
+        let mut archive = HuffmanCodec::encode_to_container(source).unwrap();
+        archive.extend_from_slice(&[0xFF, 0xA5, 0x00, 0xFF]);
+        assert_eq!(HuffmanCodec::decode_from_container(&archive).unwrap(), source);
+        assert_eq!(ByteHuffmanCodec::decode_bytes(&archive).unwrap(), source.as_bytes());
+        let mut output = vec![];
+        HuffmanCodec::decode_reader(&archive[..], &mut output).unwrap();
+        assert_eq!(output, source.as_bytes());
+
+        let uncounted = with_config(&archive, |config| {
+            config.lines().filter(|line| !line.starts_with("count:")).map(|line| format!("{}\n", line)).collect()
+        });
+        assert!(matches!(HuffmanCodec::decode_from_container(&uncounted), Err(HuffmanError::MalformedConfig(_))));
+        assert!(matches!(HuffmanCodec::decode_reader(&uncounted[..], vec![]), Err(HuffmanError::MalformedConfig(_))));
+    }
+}
+
+#[test]
+fn bit_writer_reader_roundtrip() {
+    let mut rng = Rng(0xB175);
+    // 覆盖补位为 0 与跨越内部缓冲区的长度
+    for len in [0usize, 1, 7, 8, 9, 64, 8 * 8192 + 3, 20_000 * 8 + 5] {
+        let bits = (0..len).map(|_| rng.below(2) == 1).collect::<Vec<_>>();
+        let mut out = vec![];
+        let mut writer = BitWriter::new(&mut out);
+        writer.write_bits(&bits).unwrap();
+        let space = writer.finish().unwrap();
+        assert_eq!(out.len(), len.div_ceil(8), "len {}", len);
+        assert_eq!(space as usize, out.len() * 8 - len, "len {}", len);
+
+        let mut reader = BitReader::new(out.as_slice(), space);
+        let mut read = vec![];
+        while let Some(bit) = reader.read_bit().unwrap() {
+            read.push(bit);
+        }
+        assert_eq!(read, bits, "len {}", len);
+        assert_eq!(reader.position(), len as u64);
+    }
+}
+
+#[test]
+fn bit_reader_checks_padding() {
+    // 高位在前：0b1010_0000 的前 3 位为 1 0 1，低 5 位为补位
+    let mut reader = BitReader::new(&[0b1010_0000u8][..], 5);
+    let bits = std::iter::from_fn(|| reader.read_bit().unwrap()).collect::<Vec<_>>();
+    assert_eq!(bits, [true, false, true]);
+
+    let mut reader = BitReader::new(&[0b1010_0001u8][..], 5);
+    assert!(matches!(reader.read_bit(), Err(HuffmanError::InvalidPadding)));
+    let mut reader = BitReader::new(&[][..], 3);
+    assert!(matches!(reader.read_bit(), Err(HuffmanError::InvalidPadding)));
+
+    // 读到一半结束时，剩余的位必须是补位 0，且之后没有多余的字节
+    let mut reader = BitReader::new(&[0b1100_0000u8][..], 0);
+    reader.read_bit().unwrap();
+    reader.read_bit().unwrap();
+    reader.finish().unwrap();
+    let mut reader = BitReader::new(&[0b1100_0000u8, 0][..], 0);
+    reader.read_bit().unwrap();
+    assert!(matches!(reader.finish(), Err(HuffmanError::InvalidPadding)));
+}
+
+#[test]
+fn concatenated_archives_roundtrip() {
+    let mut rng = Rng(0xF4A3);
+    let alphabet = "abc 日志\n".chars().collect::<Vec<_>>();
+    // 文本、字节、原样保存与分块压缩文件混合拼接
+    let texts = (0..5).map(|i| rng.text_from(&alphabet, 50 * i + 100)).collect::<Vec<_>>();
+    let mut stream = vec![];
+    let mut expected = vec![];
+    for text in &texts {
+        stream.extend(HuffmanCodec::encode_to_container(text).unwrap());
+        expected.push(text.as_bytes().to_vec());
+    }
+    let bytes = (0..3000).map(|_| rng.below(16) as u8).collect::<Vec<_>>();
+    stream.extend(ByteHuffmanCodec::encode_bytes(&bytes).unwrap());
+    stream.extend(ByteHuffmanCodec::encode_bytes(b"x").unwrap());
+    stream.extend(ByteHuffmanCodec::encode_blocks(&bytes, 1000, 2).unwrap());
+    expected.extend([bytes.clone(), b"x".to_vec(), bytes]);
+    // 旧版本的压缩文件：版本 3 没有标志位，版本 1 也没有 CRC32
+    let legacy_text = &"legacy 日志\n".repeat(20);
+    let legacy = HuffmanCodec::encode_to_container(legacy_text).unwrap();
+    assert_eq!(legacy[5], 0);
+    stream.extend([&b"HFM1\x03"[..], &legacy[6..]].concat());
+    stream.extend([&b"HFM1\x01"[..], &legacy[10..]].concat());
+    expected.extend([legacy_text.as_bytes().to_vec(), legacy_text.as_bytes().to_vec()]);
+
+    assert_eq!(HuffmanCodec::decode_all(stream.as_slice()).unwrap(), expected);
+    assert!(HuffmanCodec::decode_all(&[][..]).unwrap().is_empty());
+    // 最后一个压缩文件被截断
+    assert!(HuffmanCodec::decode_all(&stream[..stream.len() - 1]).is_err());
+}
+
+#[test]
+fn decode_exact_leaves_trailing_bytes() {
+    let mut rng = Rng(0x7A11);
+    let garbage = (0..100).map(|_| rng.below(256) as u8).collect::<Vec<_>>();
+    let text = rng.text_from(&"abc 日志\n".chars().collect::<Vec<_>>(), 5000);
+    let bytes = (0..3000).map(|_| rng.below(16) as u8).collect::<Vec<_>>();
+    let cases = [
+        (HuffmanCodec::encode_to_container(&text).unwrap(), text.as_bytes().to_vec()),
+        (HuffmanCodec::encode_to_container("x").unwrap(), b"x".to_vec()),
+        (ByteHuffmanCodec::encode_bytes(&bytes).unwrap(), bytes.clone()),
+        (ByteHuffmanCodec::encode_bytes(b"x").unwrap(), b"x".to_vec()),
+        (ByteHuffmanCodec::encode_blocks(&bytes, 1000, 2).unwrap(), bytes.clone()),
+    ];
+    for (archive, expected) in cases {
+        let mut stream = archive.clone();
+        stream.extend_from_slice(&garbage);
+        // 小缓冲区使解码跨越多次 `fill_buf`
+        let mut reader = std::io::BufReader::with_capacity(7, Cursor::new(&stream));
+        let mut output = vec![];
+        let outcome = HuffmanCodec::decode_exact(&mut reader, &mut output).unwrap();
+        assert_eq!(output, expected);
+        assert_eq!(outcome.bytes_consumed, archive.len() as u64);
+        let mut rest = vec![];
+        std::io::Read::read_to_end(&mut reader, &mut rest).unwrap();
+        assert_eq!(rest, garbage);
+        assert!(HuffmanCodec::decode_exact(&mut &archive[..archive.len() - 1], vec![]).is_err());
+    }
+}
+
+#[test]
+fn merged_weights_match_whole_input() {
+    let parts = samples();
+    let whole = CharWeightMap::build(&parts.concat());
+    let mut merged = CharWeightMap::build("");
+    parts.iter().for_each(|part| merged.merge(&CharWeightMap::build(part)).unwrap());
+    assert_eq!(merged.inner, whole.inner);
+
+    let collected = parts.iter().map(|part| CharWeightMap::build(part)).collect::<Result<CharWeightMap, _>>().unwrap();
+    assert_eq!(collected.inner, whole.inner);
+
+    // 同一字符的权重之和超出范围
+    let huge = CharWeightMap::from_map(vec![('a', u64::MAX)].into_iter().collect());
+    assert!(matches!(merged.merge(&huge), Err(HuffmanError::WeightOverflow)));
+    let overflow = vec![huge.clone(), huge].into_iter().collect::<Result<CharWeightMap, _>>();
+    assert!(matches!(overflow, Err(HuffmanError::WeightOverflow)));
+}
+
+#[test]
+fn degenerate_tree_does_not_overflow() {
+    // 权重 1, 2, 4, 8, ... 得到每层只有一个叶子结点的树
+    let mut weights = CharWeightMap::build("");
+    for i in 0..63 {
+        weights.inner.insert(char::from_u32(0x4E00 + i).unwrap(), 1 << i);
+    }
+    let bit_map = HuffmanBinaryMap::build(&HuffmanTree::build(weights).unwrap()).unwrap();
+    assert_eq!(bit_map.length_stats().max, 62);
+
+    // 手动连接出远超 u64 权重所能达到的深度
+    let depth = 5000;
+    let leaf = |i: u32| {
+        let mut tree = HuffmanTree::new();
+        tree.value = char::from_u32(0x4E00 + i);
+        Rc::new(RefCell::new(tree))
+    };
+    let mut root = leaf(depth);
+    for i in (0..depth).rev() {
+        let mut tree = HuffmanTree::new();
+        tree.left = Some(leaf(i));
+        tree.right = Some(root);
+        root = Rc::new(RefCell::new(tree));
+    }
+    let bit_map = HuffmanBinaryMap::build(&root).unwrap();
+    assert_eq!(bit_map.len(), depth as usize + 1);
+    assert_eq!(bit_map.get(&char::from_u32(0x4E00 + 3).unwrap()), Some(&[true, true, true, false][..]));
+    assert_eq!(bit_map.length_stats().max, depth as usize);
+}
+
+#[test]
+fn weights_from_map_and_pairs() {
+    let source = "hello weights 权重";
+    let counted = CharWeightMap::build(source);
+    let from_map = CharWeightMap::from_map(counted.inner.clone());
+    assert_eq!(from_map.inner, counted.inner);
+    // 同一字符分多次给出时权重相加
+    let from_pairs = source.chars().map(|ch| (ch, 1)).collect::<CharWeightMap>();
+    assert_eq!(from_pairs.inner, counted.inner);
+
+    let (payload, config) = HuffmanCodec::encode_with_weights(source, &from_pairs).unwrap();
+    assert_eq!(HuffmanCodec::decode(&payload, &DecodeConfig::build(&config).unwrap()).unwrap(), source);
+    let table = CodeTable::from_weights(vec![('a', 5), ('b', 1)].into_iter().collect()).unwrap();
+    let codec = HuffmanCodec::with_table(&table);
+    assert_eq!(codec.decode(&codec.encode("abba").unwrap()).unwrap(), "abba");
+}
+
+#[test]
+fn fibonacci_weights_give_long_codes() {
+    // 斐波那契权重使树退化成一条链，60 个字符时最长的编码为 59 位
+    let alphabet = (0..60).map(|i| char::from_u32('一' as u32 + i).unwrap()).collect::<Vec<_>>();
+    let mut weights = CharWeightMap::build("");
+    let (mut a, mut b) = (1u64, 1u64);
+    for ch in &alphabet {
+        weights.inner.insert(*ch, a);
+        let next = a + b;
+        a = b;
+        b = next;
+    }
+    let bit_map = HuffmanBinaryMap::build(&HuffmanTree::build(weights.clone()).unwrap()).unwrap();
+    assert_eq!(bit_map.length_stats().max, 59);
+    assert!(bit_map.code_lengths().values().any(|len| *len > 32));
+
+    let mut rng = Rng(0xF1B0);
+    let source = rng.text_from(&alphabet, 2000);
+    let (bytes, config) = HuffmanCodec::encode_with_weights(&source, &weights).unwrap();
+    let decode_map = DecodeConfig::build(&config).unwrap();
+    assert_eq!(HuffmanCodec::decode(&bytes, &decode_map).unwrap(), source);
+}
+
+#[test]
+fn alphabet_limit() {
+    let source = (0..1000u32).filter_map(|i| char::from_u32(0x4E00 + i)).collect::<String>();
+    assert!(matches!(HuffmanCodec::encode_with_max_symbols(&source, 999), Err(HuffmanError::AlphabetTooLarge(999))));
+    let (payload, config) = HuffmanCodec::encode_with_max_symbols(&source, 1000).unwrap();
+    assert_eq!(HuffmanCodec::decode(&payload, &DecodeConfig::build(&config).unwrap()).unwrap(), source);
+
+    let mut output = vec![];
+    let result = HuffmanCodec::encode_reader_with_max_symbols(Cursor::new(&source), &mut output, 10, |_, _| ());
+    assert!(matches!(result, Err(HuffmanError::AlphabetTooLarge(10))));
+    assert!(output.is_empty());
+    HuffmanCodec::encode_reader_with_max_symbols(Cursor::new(&source), &mut output, 1000, |_, _| ()).unwrap();
+    assert_eq!(HuffmanCodec::decode_from_container(&output).unwrap(), source);
+}
+
+#[test]
+fn two_symbol_alphabet() {
+    // 长度 2 ~ 17 覆盖所有补位数量，以及恰好凑满整字节的情况
+    for len in 2..=17 {
+        let source = "ab".chars().cycle().take(len).collect::<String>();
+        let bit_map = HuffmanBinaryMap::build(&HuffmanTree::build(CharWeightMap::build(&source)).unwrap()).unwrap();
+        assert_eq!(bit_map.len(), 2);
+        assert!(bit_map.iter().all(|(_, code)| code.len() == 1), "len {}", len);
+        assert_ne!(bit_map.get(&'a'), bit_map.get(&'b'));
+
+        let (payload, config) = HuffmanCodec::encode(&source).unwrap();
+        assert_eq!(payload.len(), len.div_ceil(8));
+        assert!(config.starts_with(&format!("space:{}\n", padding_bits(len))), "len {}", len);
+        assert_eq!(HuffmanCodec::decode(&payload, &DecodeConfig::build(&config).unwrap()).unwrap(), source);
+        let archive = HuffmanCodec::encode_to_container(&source).unwrap();
+        assert_eq!(HuffmanCodec::decode_from_container(&archive).unwrap(), source);
+    }
+}
+
+#[test]
+fn preset_dictionary_roundtrip() {
+    let dictionary = "2024-01-01 12:00:00 INFO server: request handled in 12ms\n".repeat(20);
+    let source = (0..5).map(|i| format!("2024-03-05 08:15:4{} INFO server: request handled in {}ms\n", i, i * 7)).collect::<String>();
+    let source = source.as_str();
+    let archive = HuffmanCodec::encode_with_dictionary(&dictionary, source).unwrap();
+    assert!(archive.len() < HuffmanCodec::encode_to_container(source).unwrap().len());
+    assert!(archive.len() < source.len());
+    assert_eq!(HuffmanCodec::decode_with_dictionary(&dictionary, &archive).unwrap(), source);
+    assert!(matches!(HuffmanCodec::decode_from_container(&archive), Err(HuffmanError::TableMismatch(_))));
+    assert!(matches!(HuffmanCodec::decode_with_dictionary("other", &archive), Err(HuffmanError::TableMismatch(_))));
+
+    // 字典中没有的字符、空字典与空文本
+    for (dictionary, source) in [(dictionary.as_str(), "WARN 日志 {x}"), ("", "abcabc"), ("abc", "")] {
+        let archive = HuffmanCodec::encode_with_dictionary(dictionary, source).unwrap();
+        assert_eq!(HuffmanCodec::decode_with_dictionary(dictionary, &archive).unwrap(), source);
+    }
+    for source in samples() {
+        let archive = HuffmanCodec::encode_with_dictionary(&dictionary, &source).unwrap();
+        assert_eq!(HuffmanCodec::decode_with_dictionary(&dictionary, &archive).unwrap(), source);
+    }
+}
+
+#[test]
+fn pack_rejects_missing_codes() {
+    // 修复前只有一种字符时得到的空编码
+    let empty = HuffmanBinaryMap { inner: std::iter::once(('a', vec![])).collect() };
+    assert!(matches!(HuffmanCodec::pack("aaa", &empty), Err(HuffmanError::MissingCode('a'))));
+    let partial = HuffmanBinaryMap { inner: std::iter::once(('a', vec![false])).collect() };
+    assert!(matches!(HuffmanCodec::pack("aab", &partial), Err(HuffmanError::MissingCode('b'))));
+    assert_eq!(HuffmanCodec::pack("aaa", &partial).unwrap(), (vec![0], 5));
+
+    let weights = CharWeightMap::build("aaa");
+    assert!(matches!(HuffmanCodec::encode_with_weights("ab", &weights), Err(HuffmanError::MissingCode('b'))));
+    let table = CodeTable::from_sample("aaa").unwrap();
+    assert!(matches!(HuffmanCodec::with_table(&table).encode("ab"), Err(HuffmanError::MissingCode('b'))));
+}
+
+#[test]
+fn frames_share_decode_tree() {
+    let table = CodeTable::from_sample("hello abc 你好\n").unwrap();
+    let tree = DecodeTree::from_bit_map(table.bit_map()).unwrap();
+    for text in ["hello", "abc 你好\n", "", "aaaaaaaaa"] {
+        let (payload, space) = HuffmanCodec::pack(text, table.bit_map()).unwrap();
+        assert_eq!(HuffmanCodec::decode_with_tree(&payload, &tree, space).unwrap(), text);
+        assert_eq!(HuffmanCodec::decode_with_tree(&payload, table.decode_tree(), space).unwrap(), text);
+    }
+
+    let ambiguous = HuffmanBinaryMap { inner: vec![('a', vec![false]), ('b', vec![false, true])].into_iter().collect() };
+    assert!(matches!(DecodeTree::from_bit_map(&ambiguous), Err(HuffmanError::AmbiguousCodes)));
+}
+
+#[test]
+fn padding_bits_fill_the_last_byte() {
+    for (bits, space) in [(0, 0), (1, 7), (7, 1), (8, 0), (9, 7), (16, 0)] {
+        assert_eq!(padding_bits(bits), space, "{} bits", bits);
+        // 与 BitWriter 实际写出的补位一致
+        let mut out = vec![];
+        let mut writer = BitWriter::new(&mut out);
+        writer.write_bits(&vec![true; bits]).unwrap();
+        assert_eq!(writer.finish().unwrap(), space, "{} bits", bits);
+    }
+}
+
+#[test]
+fn eos_roundtrip() {
+    for source in samples() {
+        let (payload, weights) = HuffmanCodec::encode_with_eos(&source).unwrap();
+        assert_eq!(HuffmanCodec::decode_with_eos(&payload, &weights).unwrap(), source);
+    }
+    // 只有一种字符时每个字符与结束符各占 1 位，n + 1 为 8 的倍数时恰好凑满整字节，没有补位
+    for n in [7, 15, 63] {
+        let source = "a".repeat(n);
+        let (payload, weights) = HuffmanCodec::encode_with_eos(&source).unwrap();
+        assert_eq!(payload.len() * 8, n + 1);
+        assert_eq!(HuffmanCodec::decode_with_eos(&payload, &weights).unwrap(), source);
+    }
+    let (mut payload, weights) = HuffmanCodec::encode_with_eos("end of stream").unwrap();
+    assert!(matches!(
+        HuffmanCodec::decode_with_eos(&payload[..payload.len() - 1], &weights),
+        Err(HuffmanError::UnexpectedEof)
+    ));
+    payload.push(0xFF);
+    assert!(matches!(HuffmanCodec::decode_with_eos(&payload, &weights), Err(HuffmanError::InvalidPadding)));
+}
+
+#[test]
+fn sampled_encoding_escapes_unseen_symbols() {
+    // 尾部出现样本中没有的字符，包括最小的码点与 4 字节字符
+    let mut text = "the quick brown fox\u{1} ".repeat(50);
+    text.push_str("\0 jumps 😀 over Ωmega \u{10FFFF}\0");
+    // 样本截止在多字节字符中间
+    let short = "ab😀😀cd€".to_owned();
+    let sizes = [0, 1, 7, 100, text.len(), text.len() * 2];
+    let cases = sizes.iter().map(|n| (&text, *n)).chain((0..=short.len()).map(|n| (&short, n)));
+    for (source, sample) in cases {
+        let mut archive = vec![];
+        let report = HuffmanCodec::encode_sampled(source.as_bytes(), &mut archive, sample).unwrap();
+        assert_eq!(report.compressed, archive.len());
+        assert_eq!(HuffmanCodec::decode_from_container(&archive).unwrap(), *source, "sample {}", sample);
+        let mut out = vec![];
+        HuffmanCodec::decode_reader(archive.as_slice(), &mut out).unwrap();
+        assert_eq!(out, source.as_bytes());
+    }
+    let mut archive = vec![];
+    HuffmanCodec::encode_sampled(&b""[..], &mut archive, 16).unwrap();
+    assert_eq!(HuffmanCodec::decode_from_container(&archive).unwrap(), "");
+}
+
+#[test]
+fn archive_try_from_validates_header() {
+    let text = "typed archive entry point ".repeat(10);
+    let meta = ContainerMeta { filename: Some("entry.txt".to_owned()), mtime: Some(1_600_000_000) };
+    let bytes = HuffmanCodec::encode_to_container_with_meta(&text, meta.clone()).unwrap();
+    let archive = HfmArchive::try_from(&bytes[..]).unwrap();
+    assert_eq!(archive.meta(), &meta);
+    assert!(!archive.is_bytes() && !archive.is_dir());
+    assert_eq!(archive.decode().unwrap(), text.as_bytes());
+    // 字节、原样保存、配置在末尾与分块的压缩文件
+    let archives = vec![
+        (rust_huffman::compress(&[0, 1, 1, 255, 255, 255]), vec![0, 1, 1, 255, 255, 255]),
+        (rust_huffman::compress(b"x"), b"x".to_vec()),
+        (HuffmanCodec::encode_to_container_with_footer(&text).unwrap(), text.clone().into_bytes()),
+        (ByteHuffmanCodec::encode_blocks(text.as_bytes(), 64, 2).unwrap(), text.clone().into_bytes()),
+    ];
+    for (bytes, original) in archives {
+        assert_eq!(HfmArchive::try_from(&bytes[..]).unwrap().decode().unwrap(), original);
+    }
+
+    let bytes = HuffmanCodec::encode_to_container(&text).unwrap();
+    assert!(matches!(HfmArchive::try_from(&b"HFM"[..]), Err(HuffmanError::InvalidMagic)));
+    assert!(matches!(HfmArchive::try_from(&bytes[..12]), Err(HuffmanError::UnexpectedEof)));
+    let mut version = bytes.clone();
+    version[4] = 99;
+    assert!(matches!(HfmArchive::try_from(&version[..]), Err(HuffmanError::UnsupportedVersion(99))));
+    // 文件头完好而 CRC32 不符时，解析成功、解压时报错
+    let mut checksum = bytes.clone();
+    checksum[6] ^= 1;
+    let archive = HfmArchive::try_from(&checksum[..]).unwrap();
+    assert!(matches!(archive.decode(), Err(HuffmanError::ChecksumMismatch)));
+}
+
+#[test]
+fn reused_codec_matches_stateless_encode() {
+    let mut codec = HuffmanCodec::new();
+    let mut out = vec![];
+    // 依次编码不同的文本，上一次的权重与编码表不会影响下一次
+    for source in samples().iter().chain(samples().iter().rev()) {
+        out.clear();
+        out.push(0xAA);
+        let config = codec.encode_into(source, &mut out).unwrap();
+        let expected = HuffmanCodec::encode_with_weights(source, &CharWeightMap::build(source)).unwrap();
+        assert_eq!(out[0], 0xAA, "追加到已有数据之后");
+        assert_eq!((out[1..].to_vec(), config), expected);
+        assert_eq!(HuffmanCodec::encode(source).unwrap(), expected);
+    }
+}
+
+#[test]
+fn estimate_matches_actual_report() {
+    // 包括原样保存的短文本与空文本
+    for text in samples().iter().map(String::as_str).chain(["", "x", "ab"]) {
+        let (_, report) = HuffmanCodec::encode_with_report(text).unwrap();
+        assert_eq!(HuffmanCodec::estimate(text).unwrap(), report);
+        assert_eq!(HuffmanCodec::estimate_reader(text.as_bytes()).unwrap(), report);
+    }
+}
+
+#[test]
+fn bit_order_roundtrip() {
+    // 先写入的位在高位 / 低位，补位总是最后写入的几位
+    let bits = [true, true, false, true];
+    for (order, byte) in [(BitOrder::MsbFirst, 0b1101_0000u8), (BitOrder::LsbFirst, 0b0000_1011)] {
+        let mut out = vec![];
+        let mut writer = BitWriter::with_order(&mut out, order);
+        writer.write_bits(&bits).unwrap();
+        assert_eq!(writer.finish().unwrap(), 4);
+        assert_eq!(out, [byte]);
+        let mut reader = BitReader::with_order(out.as_slice(), 4, order);
+        assert_eq!(std::iter::from_fn(|| reader.read_bit().unwrap()).collect::<Vec<_>>(), bits);
+    }
+
+    for text in samples() {
+        for order in [BitOrder::MsbFirst, BitOrder::LsbFirst] {
+            let archive = HuffmanCodec::encode_to_container_with_order(&text, order).unwrap();
+            assert_eq!(HuffmanCodec::decode_from_container(&archive).unwrap(), text);
+            let mut out = vec![];
+            HuffmanCodec::decode_reader(archive.as_slice(), &mut out).unwrap();
+            assert_eq!(out, text.as_bytes());
+        }
+    }
+}
+
+#[test]
+fn tree_from_weight_slice() {
+    // 权重 1、1、2：a 与 b 先合并，c 独占一侧
+    let root = HuffmanTree::from_weights(&[('c', 2), ('b', 1), ('a', 1)]).unwrap();
+    let codes = root.borrow().leaves().into_iter().map(|leaf| (leaf.symbol, leaf.code)).collect::<Vec<_>>();
+    assert_eq!(codes, [('c', vec![false]), ('a', vec![true, false]), ('b', vec![true, true])]);
+
+    // 与顺序无关，与由权重表构建的树一致；重复的符号权重相加
+    let text = "abracadabra";
+    let from_map = HuffmanBinaryMap::build(&HuffmanTree::build(CharWeightMap::build(text)).unwrap()).unwrap();
+    let mut pairs = text.chars().map(|ch| (ch, 1)).collect::<Vec<_>>();
+    pairs.reverse();
+    let from_slice = HuffmanBinaryMap::build(&HuffmanTree::from_weights(&pairs).unwrap()).unwrap();
+    assert_eq!(from_slice.inner, from_map.inner);
+}
+
+/// 与 `fuzz/` 中的模糊测试相同：截断或翻转任意一位后的压缩文件只能解压失败或解出内容，不能 panic
+#[test]
+fn corrupted_archives_never_panic() {
+    let text = "corrupt me, corrupt me 😀\n";
+    let archives = [
+        HuffmanCodec::encode_to_container(text).unwrap(),
+        HuffmanCodec::encode_to_container_with_footer(text).unwrap(),
+        ByteHuffmanCodec::encode_blocks(text.as_bytes(), 8, 1).unwrap(),
+        ByteHuffmanCodec::encode_rle_then_huffman(&[b'z'; 300]).unwrap(),
+    ];
+    for archive in &archives {
+        for len in 0..archive.len() {
+            let _ = HuffmanCodec::decode_from_container(&archive[..len]);
+        }
+        for bit in 0..archive.len() * 8 {
+            let mut corrupted = archive.clone();
+            corrupted[bit / 8] ^= 1 << (bit % 8);
+            let _ = HuffmanCodec::decode_from_container(&corrupted);
+            let _ = HuffmanCodec::decode_reader(corrupted.as_slice(), &mut vec![]);
+        }
+    }
+}
+
+/// 超过 1 MiB 的文本走多线程统计，结果须与逐个字符统计相同，包括切分点附近的多字节字符
+#[cfg(feature = "parallel")]
+#[test]
+fn parallel_weights_match_serial() {
+    let mut rng = Rng(0xC0FF_EE00_1234_5678);
+    let alphabet = ['a', 'b', '\n', 'é', '你', '😀'];
+    let mut source = String::new();
+    while source.len() < 3 << 19 {
+        source.push_str(&rng.text_from(&alphabet, 4096));
+        source.push(rng.any_char());
+    }
+    let chars = source.chars().collect::<Vec<_>>();
+    let serial = CharWeightMap::from_symbols(&chars);
+    let parallel = CharWeightMap::build(&source);
+    assert_eq!(parallel.inner, serial.inner);
+    assert_eq!(parallel.inner.values().sum::<u64>(), chars.len() as u64);
+}
+
+/// 配置中的容量与长度前缀来自文件本身，数值再大也只能得到错误或正确的结果，不能按其预先分配内存
+#[test]
+fn implausible_sizes_in_header_are_not_trusted() {
+    let text = &"hello hello hello 你好\n".repeat(10);
+    let archive = HuffmanCodec::encode_to_container(text).unwrap();
+    assert_eq!(archive[5], 0);
+    let crafted = with_config(&archive, |config| {
+        let capacity = config.lines().find(|line| line.starts_with("capacity:")).unwrap();
+        config.replacen(capacity, "capacity:18446744073709551615", 1)
+    });
+    assert_eq!(&HuffmanCodec::decode_from_container(&crafted).unwrap(), text);
+
+    // 配置长度远超文件实际大小
+    let mut truncated = archive[..14].to_vec();
+    truncated[10..14].copy_from_slice(&u32::MAX.to_le_bytes());
+    assert!(matches!(HuffmanCodec::decode_from_container(&truncated), Err(HuffmanError::UnexpectedEof)));
+}
+
+/// 字典压缩文件中补充的权重与字典的权重相加，篡改后超出范围时返回错误而不是溢出
+#[test]
+fn dictionary_weight_overflow_is_rejected() {
+    let dictionary = "aaaa bbb cc d\n".repeat(10);
+    let archive = HuffmanCodec::encode_with_dictionary(&dictionary, &"aaab 日志\n".repeat(20)).unwrap();
+    assert_eq!(archive[5], 0);
+    let crafted = with_config(&archive, |config| format!("{}U+0061:18446744073709551615\n", config));
+    let result = HuffmanCodec::decode_with_dictionary(&dictionary, &crafted);
+    assert!(matches!(result, Err(HuffmanError::WeightOverflow)), "{:?}", result);
+}
+
+/// `verify` 解压后与原文逐字节比较，任意一个字节不同都返回 `false`
+#[test]
+fn verify_detects_a_changed_byte() {
+    let text = "verify me, verify me 日志\n".repeat(10);
+    let original = text.as_bytes();
+    let archives = [HuffmanCodec::encode_to_container(&text).unwrap(), ByteHuffmanCodec::encode_bytes(original).unwrap()];
+    for archive in &archives {
+        assert!(HuffmanCodec::verify(original, archive).unwrap());
+        for i in [0, original.len() / 2, original.len() - 1] {
+            let mut changed = original.to_vec();
+            changed[i] ^= 1;
+            assert!(!HuffmanCodec::verify(&changed, archive).unwrap(), "byte {}", i);
+        }
+        assert!(!HuffmanCodec::verify(&original[1..], archive).unwrap());
+    }
+}
+
+/// 目录压缩后解压出相同的树，嵌套目录、空目录与空文件都保留；路径会写到解压目录之外的压缩文件被拒绝
+#[test]
+fn directory_roundtrip() {
+    let root = std::env::temp_dir().join(format!("rust-huffman-dir-{}", std::process::id()));
+    let _ = fs::remove_dir_all(&root);
+    let (src, dest) = (root.join("src"), root.join("dest"));
+    fs::create_dir_all(src.join("nested/deeper")).unwrap();
+    fs::create_dir_all(src.join("empty")).unwrap();
+    fs::write(src.join("top.txt"), "top level 日志\n".repeat(20)).unwrap();
+    fs::write(src.join("nested/inner.bin"), (0..=255u8).collect::<Vec<_>>()).unwrap();
+    fs::write(src.join("nested/deeper/empty.txt"), "").unwrap();
+
+    let archive = ByteHuffmanCodec::encode_dir(&src).unwrap();
+    assert!(ByteHuffmanCodec::is_dir_archive(&archive));
+    ByteHuffmanCodec::decode_dir(&archive, &dest).unwrap();
+    for file in ["top.txt", "nested/inner.bin", "nested/deeper/empty.txt"] {
+        assert_eq!(fs::read(dest.join(file)).unwrap(), fs::read(src.join(file)).unwrap(), "{}", file);
+    }
+    assert!(dest.join("empty").is_dir());
+
+    // 手工构造只有一个文件项的目录压缩文件
+    for path in ["../escaped.txt", "nested/../../escaped.txt", "/escaped.txt", "./escaped.txt", ""] {
+        let mut bundle = vec![1u8];
+        bundle.extend_from_slice(&(path.len() as u32).to_le_bytes());
+        bundle.extend_from_slice(path.as_bytes());
+        bundle.extend_from_slice(&5u64.to_le_bytes());
+        bundle.extend_from_slice(b"owned");
+        let mut crafted = ByteHuffmanCodec::encode_bytes(&bundle).unwrap();
+        // 设置 FLAG_DIR
+        crafted[5] |= 4;
+        assert!(ByteHuffmanCodec::is_dir_archive(&crafted));
+        let result = ByteHuffmanCodec::decode_dir(&crafted, &root.join("crafted"));
+        assert!(matches!(result, Err(HuffmanError::InvalidPath(ref p)) if p == path), "{:?}: {:?}", path, result);
+    }
+    assert!(!root.join("escaped.txt").exists());
+    fs::remove_dir_all(&root).unwrap();
+}
+
+/// 同一输入多次编码的结果完全相同，不受权重表遍历顺序的影响
+#[test]
+fn encoding_is_deterministic() {
+    for source in samples() {
+        assert_eq!(HuffmanCodec::encode(&source).unwrap(), HuffmanCodec::encode(&source).unwrap());
+        assert_eq!(HuffmanCodec::encode_to_container(&source).unwrap(), HuffmanCodec::encode_to_container(&source).unwrap());
+    }
+}
+
+/// 任意符号序列的编码与解码；权重之和超出范围时返回 `WeightOverflow`，不按权重预先分配内存
+#[test]
+fn symbol_weight_overflow_is_rejected() {
+    let symbols = [3u16, 1, 4, 1, 5, 9, 2, 6, 5, 3, 5];
+    let (payload, weights) = HuffmanCodec::encode_symbols(&symbols).unwrap();
+    assert_eq!(weights.total().unwrap(), symbols.len() as u64);
+    assert_eq!(HuffmanCodec::decode_symbols(&payload, &weights).unwrap(), symbols);
+
+    let huge = CharWeightMap::from_map(vec![('a', u64::MAX), ('b', 1)].into_iter().collect());
+    assert!(matches!(huge.total(), Err(HuffmanError::WeightOverflow)));
+    assert!(matches!(HuffmanCodec::decode_symbols(&[0xFF], &huge), Err(HuffmanError::WeightOverflow)));
+    // 只有一个符号时权重之和不溢出，数据不足时在读取中途报错，而不是先申请 `u64::MAX` 个符号的内存
+    let single = CharWeightMap::from_map(vec![(7u8, u64::MAX)].into_iter().collect());
+    assert!(HuffmanCodec::decode_symbols(&[0], &single).is_err());
+}